@@ -0,0 +1,473 @@
+use crate::events::Level;
+use crate::sim::{Intervals, Link, Mode, Topology};
+use crate::tangle::{Promotion, TsaConfig};
+
+use std::fmt::Display;
+use std::fs;
+use std::str::FromStr;
+
+const USAGE: &str = "\
+Usage: wurts-up [OPTIONS]
+
+Options:
+    --config <path>               read options from a file with one `key = value` per line
+    --tps-in <n>                  gossiped transactions per second of a single node, at most 1000 [default: 2]
+    --tps-out <n>                 own transactions per second (of every node), at most 1000 [default: 1]
+    --milestone-interval <secs>   time between two milestones [default: 10]
+    --invalid-interval <secs>     time between two invalid transactions (currently unused) [default: 5]
+    --ytrsi-delta <n,..>          C1 of the tip score [default: 2]
+    --otrsi-delta <n,..>          C2 of the tip score [default: 7]
+    --below-max-depth <n,..>      M of the tip score [default: 15]
+    --tsa <name,..>               tip selection strategy: heuristic, urts (uniform random), or mcmc[:alpha]
+                                  (random walk, alpha defaults to 0.1); the nodes of a network take turns in
+                                  using the given ones [default: heuristic]
+    --fixture <name|path>         initial tangle: one-milestone, two-milestones, reversed-arrival,
+                                  out-of-order-milestones, or a fixture file [default: one-milestone]
+    --load <path>                 start from a saved tangle instead of a fixture
+    --save <path>                 save the final tangle of every run
+    --snapshot <path>             start from a local snapshot instead of a fixture
+    --snapshot-out <path>         write a local snapshot at the final LSMI of every run
+    --db <path>                   keep evicted vertices in an on-disk store instead of memory; the store is
+                                  overwritten, unless a saved tangle is loaded with --load
+    --mode <mode>                 realtime or virtual [default: realtime]
+    --duration <secs>             simulated time [default: 3600]
+    --nodes <n>                   number of nodes; with more than one, the nodes gossip what they issue to their
+                                  neighbours, and node 0 issues the milestones [default: 1]
+    --topology <topology>         how the nodes are connected: full, ring, or random:<min neighbours> [default: full]
+                                  (a single node is connected to its gossip source)
+    --latency <ms>                time it takes a message to travel over a link [default: 0]
+    --jitter <ms>                 random extra delay on a link, that keeps the messages in order [default: 0]
+    --reorder <ms>                random extra delay on a link, that lets messages overtake each other [default: 0]
+    --drop <p>                    probability, that a message gets lost on a link [default: 0]
+    --bandwidth <n>               messages per second a link can carry; 0 is unlimited [default: 0]
+    --request-timeout <ms>        time to wait for a requested missing parent; 0 disables the requests [default: 1000]
+    --request-retries <n>         how often a missing parent is requested again before giving up [default: 3]
+    --semi-lazy-limit <n>         the most semi-lazy tips kept apart for promotion; at least 1 [default: 10]
+    --promotion <policy>          how semi-lazy tips are selected: weighted (like the other tips, but with a lower
+                                  weight), or kept apart and never, youngest or oldest promoted by an own message
+                                  [default: weighted]
+    --confidence <p>              fraction of the tips, that have to approve a message before it counts as
+                                  practically confirmed [default: 0.95]
+    --confidence-interval <ms>    time between two samples of the confidence; 0 disables them [default: 1000]
+    --prune-depth <n>             prune what was confirmed more than n milestones below the LSMI; 0 disables
+                                  pruning (single node only) [default: 0]
+    --seed <n>                    seed of all randomness [default: 0]
+    --log-level <level>           off, info, debug or trace [default: info]
+    --log-format <format>         human or json (one object per line) [default: human]
+    --log-file <path>             write the event log to a file instead of stdout
+    --stats-out <path>            export the run statistics as JSON (*.json) or CSV (anything else)
+    --dot <path>                  write the final tangle of every run as Graphviz DOT (a duration of 0 renders the
+                                  fixture itself)
+    -h, --help                    print this message
+
+Options given on the command line take precedence over the ones in the config file. Several comma-separated
+values for C1, C2 and M run one simulation per combination, side by side with the same seed. The files written
+per run (and node) are numbered if there are several, e.g. `tangle-2-n1.dot` for the second run on node 1.";
+
+/// Everything that can be set for a simulation run without recompiling.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub tps_in: f64,
+    pub tps_out: f64,
+    pub milestone_interval: u64,
+    // NOTE: there is no producer of invalid transactions yet
+    pub invalid_interval: u64,
+    pub ytrsi_deltas: Vec<u64>,
+    pub otrsi_deltas: Vec<u64>,
+    pub below_max_depths: Vec<u64>,
+    pub tsa: String,
+    pub fixture: String,
+    pub mode: Mode,
+    pub duration: u64,
+    pub nodes: usize,
+    pub topology: Topology,
+    pub latency: u64,
+    pub jitter: u64,
+    pub reorder: u64,
+    pub drop: f64,
+    pub bandwidth: f64,
+    pub request_timeout: u64,
+    pub request_retries: u32,
+    pub semi_lazy_limit: usize,
+    pub promotion: Promotion,
+    pub confidence: f64,
+    pub confidence_interval: u64,
+    pub prune_depth: u64,
+    pub seed: u64,
+    pub log_level: Level,
+    pub log_format: LogFormat,
+    pub log_file: Option<String>,
+    pub stats_out: Option<String>,
+    pub dot: Option<String>,
+    pub load: Option<String>,
+    pub save: Option<String>,
+    pub snapshot: Option<String>,
+    pub snapshot_out: Option<String>,
+    pub db: Option<String>,
+}
+
+/// How the event log is rendered.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LogFormat {
+    Human,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown log format: {}", s)),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            tps_in: 2.0,
+            tps_out: 1.0,
+            milestone_interval: 10,
+            invalid_interval: 5,
+            ytrsi_deltas: vec![TsaConfig::default().ytrsi_delta],
+            otrsi_deltas: vec![TsaConfig::default().otrsi_delta],
+            below_max_depths: vec![TsaConfig::default().below_max_depth],
+            tsa: "heuristic".into(),
+            fixture: "one-milestone".into(),
+            mode: Mode::RealTime,
+            duration: 3600,
+            nodes: 1,
+            topology: Topology::FullMesh,
+            latency: 0,
+            jitter: 0,
+            reorder: 0,
+            drop: 0.0,
+            bandwidth: 0.0,
+            request_timeout: 1000,
+            request_retries: 3,
+            semi_lazy_limit: 10,
+            promotion: Promotion::Weighted,
+            confidence: 0.95,
+            confidence_interval: 1000,
+            prune_depth: 0,
+            seed: 0,
+            log_level: Level::Info,
+            log_format: LogFormat::Human,
+            log_file: None,
+            stats_out: None,
+            dot: None,
+            load: None,
+            save: None,
+            snapshot: None,
+            snapshot_out: None,
+            db: None,
+        }
+    }
+}
+
+impl Config {
+    /// Reads the configuration from the process arguments; prints the usage and exits if asked for help.
+    pub fn from_args() -> Result<Self, String> {
+        let args = std::env::args().skip(1).collect::<Vec<_>>();
+
+        if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+            println!("{}", USAGE);
+            std::process::exit(0);
+        }
+
+        let options = parse_args(&args)?;
+        let mut config = Self::default();
+
+        // NOTE: the config file is applied first, so that the command line can override it
+        if let Some((_, path)) = options.iter().find(|(key, _)| key == "config") {
+            let contents =
+                fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+
+            for (key, value) in parse_file(&contents)? {
+                config.set(&key, &value)?;
+            }
+        }
+
+        for (key, value) in options.iter().filter(|(key, _)| key != "config") {
+            config.set(key, value)?;
+        }
+
+        Ok(config)
+    }
+
+    /// Sets a single option by its name, as used in the config file (with `-` or `_`) and on the command line.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key.replace('_', "-").as_str() {
+            "tps-in" => self.tps_in = parse_rate(key, value)?,
+            "tps-out" => self.tps_out = parse_rate(key, value)?,
+            "milestone-interval" => self.milestone_interval = parse(key, value)?,
+            "invalid-interval" => self.invalid_interval = parse(key, value)?,
+            "ytrsi-delta" => self.ytrsi_deltas = parse_list(key, value)?,
+            "otrsi-delta" => self.otrsi_deltas = parse_list(key, value)?,
+            "below-max-depth" => self.below_max_depths = parse_list(key, value)?,
+            "tsa" => self.tsa = value.into(),
+            "fixture" => self.fixture = value.into(),
+            "mode" => self.mode = parse(key, value)?,
+            "duration" => self.duration = parse(key, value)?,
+            "nodes" => self.nodes = parse(key, value)?,
+            "topology" => self.topology = parse(key, value)?,
+            "latency" => self.latency = parse(key, value)?,
+            "jitter" => self.jitter = parse(key, value)?,
+            "reorder" => self.reorder = parse(key, value)?,
+            "drop" => self.drop = parse_probability(key, value)?,
+            "bandwidth" => self.bandwidth = parse(key, value)?,
+            "request-timeout" => self.request_timeout = parse(key, value)?,
+            "request-retries" => self.request_retries = parse(key, value)?,
+            "semi-lazy-limit" => self.semi_lazy_limit = parse_positive(key, value)?,
+            "promotion" => self.promotion = parse(key, value)?,
+            "confidence" => self.confidence = parse_probability(key, value)?,
+            "confidence-interval" => self.confidence_interval = parse(key, value)?,
+            "prune-depth" => self.prune_depth = parse(key, value)?,
+            "seed" => self.seed = parse(key, value)?,
+            "log-level" => self.log_level = parse(key, value)?,
+            "log-format" => self.log_format = parse(key, value)?,
+            "log-file" => self.log_file = Some(value.into()),
+            "stats-out" => self.stats_out = Some(value.into()),
+            "dot" => self.dot = Some(value.into()),
+            "load" => self.load = Some(value.into()),
+            "save" => self.save = Some(value.into()),
+            "snapshot" => self.snapshot = Some(value.into()),
+            "snapshot-out" => self.snapshot_out = Some(value.into()),
+            "db" => self.db = Some(value.into()),
+            _ => return Err(format!("unknown option: {}", key)),
+        }
+
+        Ok(())
+    }
+
+    /// Every combination of the given C1, C2 and M values.
+    pub fn tsa_configs(&self) -> Vec<TsaConfig> {
+        let mut tsa_configs = Vec::new();
+
+        for &ytrsi_delta in &self.ytrsi_deltas {
+            for &otrsi_delta in &self.otrsi_deltas {
+                for &below_max_depth in &self.below_max_depths {
+                    tsa_configs.push(TsaConfig {
+                        ytrsi_delta,
+                        otrsi_delta,
+                        below_max_depth,
+                    });
+                }
+            }
+        }
+
+        tsa_configs
+    }
+
+    /// How messages travel from the gossip source to a single node, or between the nodes of a network.
+    pub fn link(&self) -> Link {
+        Link {
+            latency: self.latency,
+            jitter: self.jitter,
+            reorder: self.reorder,
+            drop: self.drop,
+            bandwidth: self.bandwidth,
+        }
+    }
+
+    /// The pauses between two runs of each producer in milliseconds.
+    pub fn intervals(&self) -> Intervals {
+        Intervals {
+            gossip: pause(self.tps_in),
+            broadcast: pause(self.tps_out),
+            milestone: self.milestone_interval * 1000,
+        }
+    }
+}
+
+// NOTE: a rate of 0 disables the producer
+fn pause(tps: f64) -> u64 {
+    if tps > 0.0 {
+        (1_f64 / tps * 1000_f64) as u64
+    } else {
+        0
+    }
+}
+
+fn parse<T>(key: &str, value: &str) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value
+        .parse()
+        .map_err(|e| format!("invalid value for {}: {} ({})", key, value, e))
+}
+
+// NOTE: the scheduler runs on a millisecond clock, so a producer can't run more often than every millisecond
+fn parse_rate(key: &str, value: &str) -> Result<f64, String> {
+    let tps: f64 = parse(key, value)?;
+
+    if (0.0..=1000.0).contains(&tps) {
+        Ok(tps)
+    } else {
+        Err(format!(
+            "invalid value for {}: {} (expected 0 to 1000)",
+            key, value
+        ))
+    }
+}
+
+fn parse_probability(key: &str, value: &str) -> Result<f64, String> {
+    let p: f64 = parse(key, value)?;
+
+    if (0.0..=1.0).contains(&p) {
+        Ok(p)
+    } else {
+        Err(format!(
+            "invalid value for {}: {} (expected 0 to 1)",
+            key, value
+        ))
+    }
+}
+
+fn parse_positive(key: &str, value: &str) -> Result<usize, String> {
+    match parse(key, value)? {
+        0 => Err(format!(
+            "invalid value for {}: {} (expected at least 1)",
+            key, value
+        )),
+        n => Ok(n),
+    }
+}
+
+fn parse_list<T>(key: &str, value: &str) -> Result<Vec<T>, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value
+        .trim_matches(|c| c == '[' || c == ']')
+        .split(',')
+        .map(|value| parse(key, value.trim()))
+        .collect()
+}
+
+fn parse_args(args: &[String]) -> Result<Vec<(String, String)>, String> {
+    let mut options = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let key = arg
+            .strip_prefix("--")
+            .ok_or_else(|| format!("unexpected argument: {}", arg))?;
+
+        // NOTE: both `--key value` and `--key=value` are accepted
+        let (key, value) = match key.split_once('=') {
+            Some((key, value)) => (key.to_string(), value.to_string()),
+            None => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("missing value for --{}", key))?;
+
+                (key.to_string(), value.clone())
+            }
+        };
+
+        options.push((key, value));
+    }
+
+    Ok(options)
+}
+
+// NOTE: this is the `key = value` subset of TOML; strings may be quoted
+fn parse_file(contents: &str) -> Result<Vec<(String, String)>, String> {
+    let mut options = Vec::new();
+
+    for (number, line) in contents.lines().enumerate() {
+        // NOTE: a `#` starts a comment, unless it's inside a quoted string
+        let mut quoted = false;
+        let end = line
+            .char_indices()
+            .find(|&(_, c)| {
+                if c == '"' {
+                    quoted = !quoted;
+                }
+                c == '#' && !quoted
+            })
+            .map_or(line.len(), |(i, _)| i);
+        let line = line[..end].trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {}: expected `key = value`", number + 1))?;
+
+        options.push((
+            key.trim().to_string(),
+            value.trim().trim_matches('"').to_string(),
+        ));
+    }
+
+    Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_line_overrides_file() {
+        let mut config = Config::default();
+
+        for (key, value) in
+            parse_file("seed = 7\ntsa = \"heuristic\" # comment\nytrsi_delta = 3\nlog_file = \"run#1.log\" # 1")
+                .unwrap()
+        {
+            config.set(&key, &value).unwrap();
+        }
+
+        let args = ["--seed", "8", "--mode=virtual"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>();
+
+        for (key, value) in parse_args(&args).unwrap() {
+            config.set(&key, &value).unwrap();
+        }
+
+        assert_eq!(8, config.seed);
+        assert_eq!(vec![3], config.ytrsi_deltas);
+        assert_eq!(Some("run#1.log".into()), config.log_file);
+        assert_eq!(Mode::Virtual, config.mode);
+        assert_eq!(500, config.intervals().gossip);
+        assert!(config.set("unknown", "1").is_err());
+        assert!(config.set("seed", "abc").is_err());
+        assert!(config.set("tps-in", "2000").is_err());
+        assert!(config.set("drop", "1.5").is_err());
+        assert!(config.set("confidence", "-0.1").is_err());
+        assert!(config.set("semi-lazy-limit", "0").is_err());
+    }
+
+    #[test]
+    fn tsa_combinations() {
+        let mut config = Config::default();
+
+        config.set("ytrsi-delta", "1,2").unwrap();
+        config.set("below_max_depth", "[10, 15, 20]").unwrap();
+
+        let tsa_configs = config.tsa_configs();
+
+        assert_eq!(6, tsa_configs.len());
+        assert!(tsa_configs.iter().all(|c| c.otrsi_delta == 7));
+        assert_eq!(
+            TsaConfig {
+                ytrsi_delta: 2,
+                otrsi_delta: 7,
+                below_max_depth: 20
+            },
+            tsa_configs[5]
+        );
+    }
+}
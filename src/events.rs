@@ -0,0 +1,457 @@
+use crate::sim::Time;
+use crate::tangle::{InternedHash, MilestoneIndex, Score};
+
+use std::fmt::{self, Write as _};
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// How much detail an event carries; sinks only receive events up to their level.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[repr(u8)]
+pub enum Level {
+    Off = 0,
+    Info = 1,
+    Debug = 2,
+    Trace = 3,
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "info" => Ok(Self::Info),
+            "debug" => Ok(Self::Debug),
+            "trace" => Ok(Self::Trace),
+            _ => Err(format!("unknown log level: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Event {
+    Started {
+        tsa: &'static str,
+        ytrsi_delta: u64,
+        otrsi_delta: u64,
+        below_max_depth: u64,
+        seed: u64,
+    },
+    // NOTE: the wall-clock time of the run is only in the report, so that the same seed gives the same events
+    Finished,
+    Inserted {
+        id: InternedHash,
+        ma: InternedHash,
+        pa: InternedHash,
+        own: bool,
+    },
+    Rejected {
+        id: InternedHash,
+        reason: &'static str,
+    },
+    MissingParent {
+        id: InternedHash,
+        parent: InternedHash,
+    },
+    Solidified {
+        id: InternedHash,
+    },
+    Confirmed {
+        id: InternedHash,
+        index: MilestoneIndex,
+    },
+    TipAdded {
+        id: InternedHash,
+    },
+    TipRemoved {
+        id: InternedHash,
+    },
+    TipSelected {
+        id: InternedHash,
+    },
+    NoTipSelected,
+    SemiLazyAdded {
+        id: InternedHash,
+    },
+    SemiLazyRemoved {
+        id: InternedHash,
+        reason: &'static str,
+    },
+    Promoted {
+        id: InternedHash,
+    },
+    ScoreComputed {
+        id: InternedHash,
+        score: Score,
+        reason: &'static str,
+    },
+    Milestone {
+        id: InternedHash,
+        index: MilestoneIndex,
+    },
+    SolidMilestone {
+        index: MilestoneIndex,
+    },
+    Pruned {
+        index: MilestoneIndex,
+        vertices: u64,
+    },
+    // NOTE: the vertex is treated as unknown
+    StorageFailed {
+        id: InternedHash,
+    },
+    Requested {
+        id: InternedHash,
+        attempt: u32,
+    },
+    RequestFailed {
+        id: InternedHash,
+    },
+    MilestoneRequested {
+        index: MilestoneIndex,
+        attempt: u32,
+    },
+}
+
+/// A field value of an event, so that sinks can render events without knowing every variant.
+pub enum Value {
+    Int(u64),
+    Bool(bool),
+    Str(&'static str),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int(value) => write!(f, "{}", value),
+            Self::Bool(value) => write!(f, "{}", value),
+            Self::Str(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl Event {
+    pub fn level(&self) -> Level {
+        match self {
+            Self::Started { .. }
+            | Self::Finished
+            | Self::Milestone { .. }
+            | Self::SolidMilestone { .. }
+            | Self::NoTipSelected
+            | Self::Pruned { .. }
+            | Self::StorageFailed { .. }
+            | Self::RequestFailed { .. } => Level::Info,
+            Self::ScoreComputed { .. } => Level::Trace,
+            _ => Level::Debug,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Started { .. } => "started",
+            Self::Finished => "finished",
+            Self::Inserted { .. } => "inserted",
+            Self::Rejected { .. } => "rejected",
+            Self::MissingParent { .. } => "missing_parent",
+            Self::Solidified { .. } => "solidified",
+            Self::Confirmed { .. } => "confirmed",
+            Self::TipAdded { .. } => "tip_added",
+            Self::TipRemoved { .. } => "tip_removed",
+            Self::TipSelected { .. } => "tip_selected",
+            Self::NoTipSelected => "no_tip_selected",
+            Self::SemiLazyAdded { .. } => "semi_lazy_added",
+            Self::SemiLazyRemoved { .. } => "semi_lazy_removed",
+            Self::Promoted { .. } => "promoted",
+            Self::ScoreComputed { .. } => "score_computed",
+            Self::Milestone { .. } => "milestone",
+            Self::SolidMilestone { .. } => "solid_milestone",
+            Self::Pruned { .. } => "pruned",
+            Self::StorageFailed { .. } => "storage_failed",
+            Self::Requested { .. } => "requested",
+            Self::RequestFailed { .. } => "request_failed",
+            Self::MilestoneRequested { .. } => "milestone_requested",
+        }
+    }
+
+    pub fn fields(&self) -> Vec<(&'static str, Value)> {
+        use Value::*;
+
+        match *self {
+            Self::Started {
+                tsa,
+                ytrsi_delta,
+                otrsi_delta,
+                below_max_depth,
+                seed,
+            } => vec![
+                ("tsa", Str(tsa)),
+                ("c1", Int(ytrsi_delta)),
+                ("c2", Int(otrsi_delta)),
+                ("m", Int(below_max_depth)),
+                ("seed", Int(seed)),
+            ],
+            Self::Inserted { id, ma, pa, own } => vec![
+                ("id", Int(id)),
+                ("ma", Int(ma)),
+                ("pa", Int(pa)),
+                ("own", Bool(own)),
+            ],
+            Self::Rejected { id, reason } | Self::SemiLazyRemoved { id, reason } => {
+                vec![("id", Int(id)), ("reason", Str(reason))]
+            }
+            Self::MissingParent { id, parent } => vec![("id", Int(id)), ("parent", Int(parent))],
+            Self::Solidified { id }
+            | Self::RequestFailed { id }
+            | Self::StorageFailed { id }
+            | Self::TipAdded { id }
+            | Self::TipRemoved { id }
+            | Self::TipSelected { id }
+            | Self::SemiLazyAdded { id }
+            | Self::Promoted { id } => vec![("id", Int(id))],
+            Self::Finished | Self::NoTipSelected => vec![],
+            Self::Confirmed { id, index } | Self::Milestone { id, index } => {
+                vec![("id", Int(id)), ("index", Int(index))]
+            }
+            Self::ScoreComputed { id, score, reason } => vec![
+                ("id", Int(id)),
+                ("score", Str(score.name())),
+                ("reason", Str(reason)),
+            ],
+            Self::SolidMilestone { index } => vec![("index", Int(index))],
+            Self::Pruned { index, vertices } => {
+                vec![("index", Int(index)), ("vertices", Int(vertices))]
+            }
+            Self::Requested { id, attempt } => {
+                vec![("id", Int(id)), ("attempt", Int(attempt as u64))]
+            }
+            Self::MilestoneRequested { index, attempt } => {
+                vec![("index", Int(index)), ("attempt", Int(attempt as u64))]
+            }
+        }
+    }
+}
+
+/// Receives the events of a tangle.
+pub trait Subscriber: Send + Sync {
+    /// The most detailed level this subscriber is interested in.
+    fn level(&self) -> Level {
+        Level::Trace
+    }
+
+    /// `node` identifies the tangle the event happened in, if there are several.
+    fn on_event(&self, time: Time, node: Option<usize>, event: &Event);
+}
+
+/// Dispatches events to all subscribers that are interested in them.
+pub struct EventBus {
+    node: Option<usize>,
+    time: AtomicU64,
+    level: AtomicU8,
+    subscribers: RwLock<Vec<Arc<dyn Subscriber>>>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self {
+            node: None,
+            time: AtomicU64::new(0),
+            level: AtomicU8::new(Level::Off as u8),
+            subscribers: RwLock::new(Vec::new()),
+        }
+    }
+}
+
+impl EventBus {
+    pub fn subscribe(&self, subscriber: Arc<dyn Subscriber>) {
+        self.level
+            .fetch_max(subscriber.level() as u8, Ordering::Relaxed);
+        self.subscribers
+            .write()
+            .expect("poisoned subscribers")
+            .push(subscriber);
+    }
+
+    /// Sets the node that is attached to all events, for tangles that are part of a network.
+    pub fn set_node(&mut self, node: usize) {
+        self.node = Some(node);
+    }
+
+    /// Sets the time that is attached to all following events.
+    pub fn set_time(&self, time: Time) {
+        self.time.store(time, Ordering::Relaxed);
+    }
+
+    pub fn time(&self) -> Time {
+        self.time.load(Ordering::Relaxed)
+    }
+
+    pub fn enabled(&self, level: Level) -> bool {
+        level as u8 <= self.level.load(Ordering::Relaxed)
+    }
+
+    pub fn emit(&self, event: Event) {
+        let level = event.level();
+
+        if !self.enabled(level) {
+            return;
+        }
+
+        let time = self.time();
+
+        for subscriber in self
+            .subscribers
+            .read()
+            .expect("poisoned subscribers")
+            .iter()
+        {
+            if level <= subscriber.level() {
+                subscriber.on_event(time, self.node, &event);
+            }
+        }
+    }
+}
+
+/// Writes one JSON object per event and line.
+pub struct JsonSink<W> {
+    level: Level,
+    out: Mutex<W>,
+}
+
+impl<W: Write + Send> JsonSink<W> {
+    pub fn new(out: W, level: Level) -> Self {
+        Self {
+            level,
+            out: Mutex::new(out),
+        }
+    }
+}
+
+impl<W: Write + Send> Subscriber for JsonSink<W> {
+    fn level(&self) -> Level {
+        self.level
+    }
+
+    fn on_event(&self, time: Time, node: Option<usize>, event: &Event) {
+        let mut line = format!("{{\"time\":{}", time);
+
+        if let Some(node) = node {
+            write!(line, ",\"node\":{}", node).expect("writing to a string");
+        }
+
+        write!(line, ",\"event\":\"{}\"", event.name()).expect("writing to a string");
+
+        for (key, value) in event.fields() {
+            match value {
+                Value::Str(value) => write!(line, ",\"{}\":\"{}\"", key, value),
+                value => write!(line, ",\"{}\":{}", key, value),
+            }
+            .expect("writing to a string");
+        }
+
+        line.push('}');
+
+        let mut out = self.out.lock().expect("poisoned sink");
+        // NOTE: a broken log shouldn't bring down the simulation
+        let _ = writeln!(out, "{}", line);
+    }
+}
+
+/// Writes one line per event in the style of `[   1500ms] inserted       id=5 ma=1 pa=2 own=false`, with the node
+/// (as in `#2`) in front of the event name if there is one.
+pub struct HumanSink<W> {
+    level: Level,
+    out: Mutex<W>,
+}
+
+impl<W: Write + Send> HumanSink<W> {
+    pub fn new(out: W, level: Level) -> Self {
+        Self {
+            level,
+            out: Mutex::new(out),
+        }
+    }
+}
+
+impl<W: Write + Send> Subscriber for HumanSink<W> {
+    fn level(&self) -> Level {
+        self.level
+    }
+
+    fn on_event(&self, time: Time, node: Option<usize>, event: &Event) {
+        let mut line = format!("[{:>9}ms] ", time);
+
+        if let Some(node) = node {
+            write!(line, "#{:<3} ", node).expect("writing to a string");
+        }
+
+        write!(line, "{:<15}", event.name()).expect("writing to a string");
+
+        for (key, value) in event.fields() {
+            write!(line, " {}={}", key, value).expect("writing to a string");
+        }
+
+        let mut out = self.out.lock().expect("poisoned sink");
+        let _ = writeln!(out, "{}", line.trim_end());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    #[test]
+    fn sinks_filter_by_level() {
+        let bus = EventBus::default();
+        let json = Buffer::default();
+        let human = Buffer::default();
+
+        bus.subscribe(Arc::new(JsonSink::new(json.clone(), Level::Debug)));
+        bus.subscribe(Arc::new(HumanSink::new(human.clone(), Level::Info)));
+
+        bus.set_time(1500);
+        bus.emit(Event::Inserted {
+            id: 5,
+            ma: 1,
+            pa: 2,
+            own: false,
+        });
+        bus.emit(Event::ScoreComputed {
+            id: 5,
+            score: Score::NonLazy,
+            reason: "ok",
+        });
+        bus.emit(Event::Milestone { id: 5, index: 3 });
+
+        assert_eq!(
+            "{\"time\":1500,\"event\":\"inserted\",\"id\":5,\"ma\":1,\"pa\":2,\"own\":false}\n\
+             {\"time\":1500,\"event\":\"milestone\",\"id\":5,\"index\":3}\n",
+            json.contents()
+        );
+        assert_eq!(
+            "[     1500ms] milestone       id=5 index=3\n",
+            human.contents()
+        );
+    }
+}
@@ -1,105 +1,238 @@
 #![allow(dead_code, unused_variables)]
 
+mod config;
+mod events;
+mod sim;
+mod stats;
 mod tangle;
 mod utils;
 
-use tangle::{tangle, Message, MessageKind};
+use config::{Config, LogFormat};
+use events::{HumanSink, JsonSink, Subscriber};
+use sim::{Network, Simulator};
+use tangle::{storage::FileStorage, Fixture, Snapshot, Tangle};
 
-use rand::Rng;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::thread;
-use std::time::Duration;
+fn main() {
+    let config = match Config::from_args() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("Try --help for more information.");
+            std::process::exit(1);
+        }
+    };
 
-const TPS_IN: u64 = 2;
-const TPS_IN_PAUSE: u64 = (1_f64 / (TPS_IN as f64) * 1000_f64) as u64;
-const TPS_OUT: u64 = 1; // rename: submit interval?
-const TPS_OUT_PAUSE: u64 = (1_f64 / (TPS_OUT as f64) * 1000_f64) as u64;
-const MILESTONE_INTERVAL: u64 = 10;
-const INVALID_INTERVAL: u64 = 5;
+    if config.nodes == 0 {
+        eprintln!("There must be at least one node");
+        std::process::exit(1);
+    }
 
-static LAST_TX_ID: AtomicU64 = AtomicU64::new(0);
-static IS_MILESTONE: AtomicBool = AtomicBool::new(false);
+    // NOTE: the nodes of a network take turns in using the given strategies
+    let tsas = config.tsa.split(',').map(str::trim).collect::<Vec<_>>();
 
-fn main() {
-    let (last_tx_id, last_ms_index) = utils::make_tangle_1_milestone();
+    if let Some(name) = tsas
+        .iter()
+        .find(|name| tangle::tsa::by_name(name).is_none())
+    {
+        eprintln!("Unknown tip selection strategy: {}", name);
+        std::process::exit(1);
+    }
 
-    LAST_TX_ID.store(last_tx_id + 1, Ordering::Relaxed);
+    // NOTE: a saved tangle or a local snapshot takes the place of the fixture
+    let make_tangle = || match (&config.load, &config.snapshot) {
+        (Some(path), _) => Tangle::load(path),
+        (None, Some(path)) => Snapshot::load(path).map(|snapshot| Tangle::from_snapshot(&snapshot)),
+        // NOTE: anything that isn't a built-in fixture is a fixture file
+        (None, None) => match utils::make_tangle(&config.fixture) {
+            Some(tangle) => Ok(tangle),
+            None if Path::new(&config.fixture).is_file() => {
+                Fixture::load(&config.fixture).map(|fixture| fixture.build())
+            }
+            None => Err(format!("Unknown fixture: {}", config.fixture)),
+        },
+    };
 
-    let mut handles = Vec::new();
+    if let Err(e) = make_tangle() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
 
-    // insert gossiped transactions (without TSA: simply randomly picked parents with a tendency to pick more
-    // recent ones)
-    handles.push(thread::spawn(move || {
-        let mut rng = rand::thread_rng();
-        let mut ms_index = last_ms_index + 1;
+    let log: Box<dyn Write + Send> = match &config.log_file {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(BufWriter::new(file)),
+            Err(e) => {
+                eprintln!("Cannot create {}: {}", path, e);
+                std::process::exit(1);
+            }
+        },
+        None => Box::new(io::stdout()),
+    };
+
+    // NOTE: all runs share one sink, so that their events end up in the same log
+    let sink: Arc<dyn Subscriber> = match config.log_format {
+        LogFormat::Human => Arc::new(HumanSink::new(log, config.log_level)),
+        LogFormat::Json => Arc::new(JsonSink::new(log, config.log_level)),
+    };
+
+    let mut reports = Vec::new();
+    let tsa_configs = config.tsa_configs();
+
+    // NOTE: every combination of TSA parameters runs on its own tangle, but sees the same seed
+    for (run, tsa_config) in tsa_configs.iter().copied().enumerate() {
+        let run = if tsa_configs.len() > 1 {
+            Some(run + 1)
+        } else {
+            None
+        };
+
+        let mut tangles = Vec::new();
+
+        for node in 0..config.nodes {
+            let tsa = tangle::tsa::by_name(tsas[node % tsas.len()]).unwrap();
+            let mut tangle = make_tangle().unwrap();
+
+            if let Some(path) = &config.db {
+                let path = output_path(path, run, config.nodes, node);
+
+                // NOTE: a saved tangle continues the run that wrote it, so it keeps using that run's store; any
+                // other run starts with an empty one, as its ids would collide with the stored ones
+                let storage = if config.load.is_some() {
+                    FileStorage::open(&path)
+                } else {
+                    FileStorage::create(&path)
+                };
+
+                match storage {
+                    Ok(storage) => tangle = tangle.with_storage(Box::new(storage)),
+                    Err(e) => {
+                        eprintln!("Cannot open {}: {}", path, e);
+                        std::process::exit(1);
+                    }
+                }
+            }
 
-        loop {
-            thread::sleep(Duration::from_millis(TPS_IN_PAUSE));
+            tangle.events.subscribe(Arc::clone(&sink));
 
-            // Simulate gossip
-            let last = LAST_TX_ID.load(Ordering::Relaxed);
-            let ma = rng.gen_range(last - 10, last);
-            let pa = rng.gen_range(last - 10, last);
+            tangles.push(
+                tangle
+                    .with_tip_selector(tsa)
+                    .with_tsa_config(tsa_config)
+                    .with_semi_lazy_pool(config.semi_lazy_limit, config.promotion),
+            );
+        }
 
-            let i = LAST_TX_ID.fetch_add(1, Ordering::Relaxed);
+        let result = if config.nodes > 1 {
+            let mut network = Network::new(
+                tangles,
+                config.topology,
+                config.seed,
+                config.intervals(),
+                config.link(),
+            )
+            .with_solidifier(config.request_timeout, config.request_retries)
+            .with_confidence(config.confidence, config.confidence_interval);
+
+            reports.extend(network.run(config.mode, config.duration * 1000));
+
+            (0..network.len())
+                .try_for_each(|node| write_outputs(&config, network.tangle(node), run, node))
+        } else {
+            let mut sim = Simulator::new(tangles.remove(0), config.seed, config.intervals())
+                .with_prune_depth(config.prune_depth)
+                .with_link(config.link())
+                .with_solidifier(config.request_timeout, config.request_retries)
+                .with_confidence(config.confidence, config.confidence_interval);
 
-            if IS_MILESTONE.compare_and_swap(true, false, Ordering::Relaxed) {
-                println!(
-                    "[GOSSIP_IN ] Received milestone with index {} and parents ({},{})",
-                    ms_index, ma, pa
-                );
+            reports.push(sim.run(config.mode, config.duration * 1000));
 
-                tangle().insert_gossip(
-                    i,
-                    Message::new((), MessageKind::Milestone(ms_index)),
-                    ma,
-                    pa,
-                );
+            write_outputs(&config, &sim.tangle, run, 0)
+        };
 
-                ms_index += 1;
-            } else {
-                println!(
-                    "[GOSSIP_IN ] Received transaction: {} with parents ({},{})",
-                    i, ma, pa
-                );
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    for report in &reports {
+        println!("\n[REPORT    ] {}", report);
+    }
 
-                tangle().insert_gossip(i, Message::new((), MessageKind::Data), ma, pa);
+    if let Some(path) = &config.stats_out {
+        let result = File::create(path).and_then(|file| {
+            if path.ends_with(".json") {
+                sim::write_json(&reports, BufWriter::new(file))
+            } else {
+                sim::write_csv(&reports, BufWriter::new(file))
             }
+        });
+
+        if let Err(e) = result {
+            eprintln!("Cannot write {}: {}", path, e);
+            std::process::exit(1);
         }
-    }));
+    }
+}
 
-    // insert own transactions (with TSA)
-    handles.push(thread::spawn(move || loop {
-        thread::sleep(Duration::from_millis(TPS_OUT_PAUSE));
+// Writes whatever was asked for about the final state of a tangle.
+fn write_outputs(
+    config: &Config,
+    tangle: &Tangle,
+    run: Option<usize>,
+    node: usize,
+) -> Result<(), String> {
+    if let Some(path) = &config.dot {
+        let path = output_path(path, run, config.nodes, node);
+
+        fs::write(&path, tangle.to_dot()).map_err(|e| format!("Cannot write {}: {}", path, e))?;
+    }
 
-        if let Some((ma, pa)) = tangle().select_two_tips() {
-            let i = LAST_TX_ID.fetch_add(1, Ordering::Relaxed);
+    if let Some(path) = &config.save {
+        tangle.save(&output_path(path, run, config.nodes, node))?;
+    }
 
-            println!(
-                "[BROADCAST ] Created transaction with id={} and parents ({},{})",
-                i, ma, pa
-            );
+    if let Some(path) = &config.snapshot_out {
+        let lsmi = tangle.lsmi.load(Ordering::Relaxed);
 
-            tangle().insert_own(i, Message::new((), MessageKind::Data), ma, pa);
-        } else {
-            println!("tip pool empty");
-        }
-    }));
+        tangle
+            .local_snapshot(lsmi)?
+            .save(&output_path(path, run, config.nodes, node))?;
+    }
 
-    // TODO: flag an existing transaction as milestone
+    Ok(())
+}
 
-    // insert gossiped milestones (coordinator TSA: previous milestone in past cone)
-    handles.push(thread::spawn(move || {
-        loop {
-            // Issue a milestone every 10 seconds
-            thread::sleep(Duration::from_secs(MILESTONE_INTERVAL));
+// NOTE: with several runs and/or nodes, `tangle.dot` becomes `tangle-2.dot` for the second run, `tangle-n1.dot` for
+// the second node, and `tangle-2-n1.dot` for both
+fn output_path(path: &str, run: Option<usize>, nodes: usize, node: usize) -> String {
+    let mut suffix = Vec::new();
 
-            IS_MILESTONE.compare_and_swap(false, true, Ordering::Relaxed);
-        }
-    }));
+    if let Some(run) = run {
+        suffix.push(run.to_string());
+    }
+
+    if nodes > 1 {
+        suffix.push(format!("n{}", node));
+    }
 
-    for handle in handles.pop() {
-        handle.join().expect("error joining handle");
+    if suffix.is_empty() {
+        path.to_string()
+    } else {
+        numbered(path, &suffix.join("-"))
+    }
+}
+
+fn numbered(path: &str, suffix: &str) -> String {
+    match path.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && !extension.contains('/') => {
+            format!("{}-{}.{}", stem, suffix, extension)
+        }
+        _ => format!("{}-{}", path, suffix),
     }
 }
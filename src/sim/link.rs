@@ -0,0 +1,100 @@
+use super::Time;
+
+use rand::{rngs::StdRng, Rng};
+
+/// How messages travel over a link. The default delivers every message instantly and in order.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Link {
+    // the time it takes a message to travel over the link in milliseconds ...
+    pub latency: Time,
+    // ... plus a random delay of up to `jitter`, which keeps the messages in order ...
+    pub jitter: Time,
+    // ... plus a random delay of up to `reorder`, which lets messages overtake each other
+    pub reorder: Time,
+    // the probability, that a message gets lost
+    pub drop: f64,
+    // the number of messages per second the link can carry; 0 is unlimited
+    pub bandwidth: f64,
+}
+
+/// The state of one direction of a link.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LinkState {
+    busy_until: Time,
+    last_arrival: Time,
+}
+
+impl Link {
+    pub fn is_instant(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Sends a message at `now`, and returns when it arrives, or `None` if it gets lost on the way.
+    pub fn send(&self, state: &mut LinkState, now: Time, rng: &mut StdRng) -> Option<Time> {
+        // NOTE: a message has to wait until the ones before it are on the wire, even if it gets lost afterwards
+        let start = now.max(state.busy_until);
+        let transmission = if self.bandwidth > 0.0 {
+            (1000.0 / self.bandwidth) as Time
+        } else {
+            0
+        };
+        state.busy_until = start + transmission;
+
+        if self.drop > 0.0 && rng.gen_bool(self.drop.min(1.0)) {
+            return None;
+        }
+
+        let mut arrival = start + transmission + self.latency;
+
+        if self.jitter > 0 {
+            arrival += rng.gen_range(0, self.jitter + 1);
+        }
+
+        arrival = arrival.max(state.last_arrival);
+        state.last_arrival = arrival;
+
+        if self.reorder > 0 {
+            arrival += rng.gen_range(0, self.reorder + 1);
+        }
+
+        Some(arrival)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn jitter_keeps_order_and_bandwidth_queues() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let link = Link {
+            latency: 100,
+            jitter: 50,
+            bandwidth: 10.0,
+            ..Link::default()
+        };
+        let mut state = LinkState::default();
+
+        let arrivals = (0..20)
+            .map(|_| link.send(&mut state, 0, &mut rng).unwrap())
+            .collect::<Vec<_>>();
+
+        assert!(arrivals.windows(2).all(|pair| pair[0] <= pair[1]));
+        // NOTE: the 20th message is only on the wire after 2 seconds
+        assert!(arrivals[19] >= 2000 + 100);
+
+        let lossy = Link {
+            drop: 1.0,
+            ..Link::default()
+        };
+
+        assert_eq!(None, lossy.send(&mut LinkState::default(), 0, &mut rng));
+        assert!(Link::default().is_instant());
+        assert_eq!(
+            Some(5),
+            Link::default().send(&mut LinkState::default(), 5, &mut rng)
+        );
+    }
+}
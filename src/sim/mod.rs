@@ -0,0 +1,437 @@
+mod link;
+mod network;
+mod report;
+mod scheduler;
+mod solidifier;
+
+pub use link::{Link, LinkState};
+pub use network::{Network, Topology};
+pub use report::{write_csv, write_json, Report};
+pub use scheduler::{Scheduler, Time};
+pub use solidifier::{Request, Solidifier};
+
+use crate::events;
+use crate::stats::Stats;
+use crate::tangle::{InternedHash, Message, MessageKind, MilestoneIndex, Tangle};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use std::collections::{HashMap, VecDeque};
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// the number of recent ids gossiped transactions pick their parents from
+const RECENT: usize = 10;
+
+/// How the simulation clock relates to the wall clock.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Mode {
+    /// Every event waits until its time has come on the wall clock.
+    RealTime,
+    /// Events are processed as fast as possible on a virtual clock.
+    Virtual,
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "realtime" => Ok(Self::RealTime),
+            "virtual" => Ok(Self::Virtual),
+            _ => Err(format!("unknown mode: {}", s)),
+        }
+    }
+}
+
+/// The pauses between two runs of each producer; a pause of 0 disables the producer.
+#[derive(Clone, Copy, Debug)]
+pub struct Intervals {
+    pub gossip: Time,
+    pub broadcast: Time,
+    pub milestone: Time,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Event {
+    Gossip,
+    Broadcast,
+    Milestone,
+    // a gossiped transaction arrives over the link from the gossip source
+    Arrive {
+        id: InternedHash,
+        ma: InternedHash,
+        pa: InternedHash,
+        milestone: Option<MilestoneIndex>,
+    },
+    // a request for a missing parent or milestone arrives at the gossip source
+    Request(Request),
+    // the confidence of the unconfirmed vertices is sampled
+    Confidence,
+}
+
+// NOTE: all producers run as events on a single scheduler, so that a run is reproducible from its seed
+pub struct Simulator {
+    pub tangle: Tangle,
+    stats: Arc<Stats>,
+    seed: u64,
+    rng: StdRng,
+    intervals: Intervals,
+    last_tx_id: u64,
+    // the most recent ids, from which gossiped transactions pick their parents
+    recent: VecDeque<InternedHash>,
+    ms_index: MilestoneIndex,
+    is_milestone: bool,
+    prune_depth: MilestoneIndex,
+    pruned_index: MilestoneIndex,
+    link: Link,
+    link_state: LinkState,
+    // the other direction of the link, which carries the requests for missing parents
+    request_state: LinkState,
+    solidifier: Solidifier,
+    // every transaction the gossip source sent and the node doesn't know yet, so that it can answer requests for
+    // them
+    sent: HashMap<InternedHash, (InternedHash, InternedHash, Option<MilestoneIndex>)>,
+    sent_milestones: HashMap<MilestoneIndex, InternedHash>,
+    // the confidence, at which a vertex counts as practically confirmed, and how often it's sampled
+    confidence_threshold: f64,
+    confidence_interval: Time,
+}
+
+impl Simulator {
+    pub fn new(tangle: Tangle, seed: u64, intervals: Intervals) -> Self {
+        // NOTE: every source of randomness is derived from the seed
+        let mut rng = StdRng::seed_from_u64(seed);
+        let tangle = tangle.with_seed(rng.gen());
+        let rng = StdRng::seed_from_u64(rng.gen());
+
+        // NOTE: a tangle started from a snapshot only knows its solid entry points
+        let mut ids = tangle
+            .vertices
+            .iter()
+            .map(|v| *v.key())
+            .chain(tangle.seps.iter().map(|sep| *sep.key()))
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+
+        let last_tx_id = ids.last().copied().unwrap_or(0);
+        let recent = ids.iter().rev().take(RECENT).rev().copied().collect();
+        let ms_index = tangle.lmi.load(Ordering::Relaxed) + 1;
+
+        let stats = Arc::new(Stats::default());
+        tangle.events.subscribe(Arc::clone(&stats) as _);
+
+        Self {
+            tangle,
+            stats,
+            seed,
+            rng,
+            intervals,
+            last_tx_id,
+            recent,
+            ms_index,
+            is_milestone: false,
+            prune_depth: 0,
+            pruned_index: 0,
+            link: Link::default(),
+            link_state: LinkState::default(),
+            request_state: LinkState::default(),
+            solidifier: Solidifier::disabled(),
+            sent: HashMap::new(),
+            sent_milestones: HashMap::new(),
+            confidence_threshold: 1.0,
+            confidence_interval: 0,
+        }
+    }
+
+    /// Puts a link between the gossip source and the tangle; by default gossip arrives instantly and in order.
+    pub fn with_link(mut self, link: Link) -> Self {
+        self.link = link;
+        self
+    }
+
+    /// Requests missing parents from the gossip source; see `Solidifier::new`.
+    pub fn with_solidifier(mut self, timeout: Time, retries: u32) -> Self {
+        self.solidifier = Solidifier::new(timeout, retries);
+        self
+    }
+
+    /// Samples the confidence of the unconfirmed vertices every `interval` milliseconds, and counts a vertex as
+    /// practically confirmed, once it reaches `threshold`; an interval of 0 disables the sampling.
+    pub fn with_confidence(mut self, threshold: f64, interval: Time) -> Self {
+        self.confidence_threshold = threshold;
+        self.confidence_interval = interval;
+        self
+    }
+
+    /// Prunes everything that was confirmed more than `depth` milestones below the LSMI; 0 disables pruning.
+    pub fn with_prune_depth(mut self, depth: MilestoneIndex) -> Self {
+        self.prune_depth = depth;
+        self
+    }
+
+    /// Runs the producers for `duration` milliseconds of simulated time.
+    pub fn run(&mut self, mode: Mode, duration: Time) -> Report {
+        let mut scheduler = Scheduler::new();
+
+        // NOTE: producers that are due at the same time run in the order they were scheduled
+        self.reschedule(&mut scheduler, Event::Milestone);
+        self.reschedule(&mut scheduler, Event::Gossip);
+        self.reschedule(&mut scheduler, Event::Broadcast);
+        self.reschedule(&mut scheduler, Event::Confidence);
+
+        let start = Instant::now();
+
+        emit_started(&self.tangle, self.seed);
+
+        self.stats
+            .sample_tips(scheduler.now(), self.tangle.num_tips());
+
+        while let Some(event) = scheduler.next_until(duration) {
+            if mode == Mode::RealTime {
+                let due = start + Duration::from_millis(scheduler.now());
+                let now = Instant::now();
+
+                if due > now {
+                    thread::sleep(due - now);
+                }
+            }
+
+            self.tangle.events.set_time(scheduler.now());
+
+            match event {
+                Event::Gossip => self.gossip(&mut scheduler),
+                Event::Broadcast => self.broadcast(),
+                Event::Milestone => self.milestone(),
+                Event::Arrive {
+                    id,
+                    ma,
+                    pa,
+                    milestone,
+                } => self.arrive(id, ma, pa, milestone),
+                Event::Request(request) => self.answer(&mut scheduler, request),
+                Event::Confidence => self.stats.sample_confidence(
+                    &self.tangle,
+                    scheduler.now(),
+                    self.confidence_threshold,
+                ),
+            }
+
+            self.reschedule(&mut scheduler, event);
+            self.solidify(&mut scheduler);
+            self.prune();
+
+            self.stats
+                .sample_tips(scheduler.now(), self.tangle.num_tips());
+        }
+
+        self.tangle.events.set_time(scheduler.now());
+        self.tangle.events.emit(events::Event::Finished);
+
+        Report {
+            elapsed: start.elapsed().as_millis() as u64,
+            ..self.report(scheduler.now())
+        }
+    }
+
+    pub fn report(&self, simulated: Time) -> Report {
+        Report {
+            node: None,
+            tsa: self.tangle.tip_selector().name(),
+            tsa_config: self.tangle.tsa_config(),
+            seed: self.seed,
+            simulated,
+            elapsed: 0,
+            vertices: self.tangle.vertices.len(),
+            lmi: self.tangle.lmi.load(Ordering::Relaxed),
+            lsmi: self.tangle.lsmi.load(Ordering::Relaxed),
+            stats: self.stats.summary(&self.tangle, simulated),
+        }
+    }
+
+    fn reschedule(&self, scheduler: &mut Scheduler<Event>, event: Event) {
+        let interval = match event {
+            Event::Gossip => self.intervals.gossip,
+            Event::Broadcast => self.intervals.broadcast,
+            Event::Milestone => self.intervals.milestone,
+            Event::Confidence => self.confidence_interval,
+            Event::Arrive { .. } | Event::Request(_) => 0,
+        };
+
+        if interval > 0 {
+            scheduler.schedule(interval, event);
+        }
+    }
+
+    fn prune(&mut self) {
+        let lsmi = self.tangle.lsmi.load(Ordering::Relaxed);
+
+        if self.prune_depth > 0 && lsmi > self.pruned_index + self.prune_depth {
+            self.pruned_index = lsmi - self.prune_depth;
+            self.tangle
+                .prune(self.pruned_index)
+                .expect("cannot evict into the storage");
+        }
+    }
+
+    fn next_id(&mut self) -> u64 {
+        self.last_tx_id += 1;
+
+        if self.recent.len() == RECENT {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(self.last_tx_id);
+
+        self.last_tx_id
+    }
+
+    // insert gossiped transactions (without TSA: simply randomly picked parents with a tendency to pick more
+    // recent ones)
+    fn gossip(&mut self, scheduler: &mut Scheduler<Event>) {
+        // Simulate gossip
+        let ma = self.pick_recent();
+        let pa = self.pick_recent();
+
+        let i = self.next_id();
+
+        let milestone = if self.is_milestone {
+            self.is_milestone = false;
+            self.ms_index += 1;
+
+            Some(self.ms_index - 1)
+        } else {
+            None
+        };
+
+        self.sent.insert(i, (ma, pa, milestone));
+
+        if let Some(index) = milestone {
+            self.sent_milestones.insert(index, i);
+        }
+
+        // NOTE: an instant link doesn't go through the scheduler, so that nothing can happen in between
+        if self.link.is_instant() {
+            self.arrive(i, ma, pa, milestone);
+        } else if let Some(arrival) =
+            self.link
+                .send(&mut self.link_state, scheduler.now(), &mut self.rng)
+        {
+            scheduler.schedule_at(
+                arrival,
+                Event::Arrive {
+                    id: i,
+                    ma,
+                    pa,
+                    milestone,
+                },
+            );
+        }
+    }
+
+    fn arrive(
+        &mut self,
+        id: InternedHash,
+        ma: InternedHash,
+        pa: InternedHash,
+        milestone: Option<MilestoneIndex>,
+    ) {
+        let kind = match milestone {
+            Some(index) => MessageKind::Milestone(index),
+            None => MessageKind::Data,
+        };
+
+        self.tangle
+            .insert_gossip(id, Message::new((), kind), ma, pa);
+
+        // NOTE: the node won't ask for what it knows, so the gossip source can forget it
+        if self.tangle.is_known(&id) {
+            self.sent.remove(&id);
+
+            if let Some(index) = milestone {
+                self.sent_milestones.remove(&index);
+            }
+        }
+    }
+
+    // request the missing parents and milestones, that are due, from the gossip source
+    fn solidify(&mut self, scheduler: &mut Scheduler<Event>) {
+        for (request, _) in self.solidifier.poll(&self.tangle, scheduler.now()) {
+            if let Some(arrival) =
+                self.link
+                    .send(&mut self.request_state, scheduler.now(), &mut self.rng)
+            {
+                scheduler.schedule_at(arrival, Event::Request(request));
+            }
+        }
+    }
+
+    // NOTE: the gossip source can only answer for the transactions it sent itself, and the answer travels over the
+    // same link as the gossip, so it can get lost again
+    fn answer(&mut self, scheduler: &mut Scheduler<Event>, request: Request) {
+        let id = match request {
+            Request::Parent(id) => id,
+            Request::Milestone(index) => match self.sent_milestones.get(&index) {
+                Some(id) => *id,
+                None => return,
+            },
+        };
+
+        if let Some(&(ma, pa, milestone)) = self.sent.get(&id) {
+            if let Some(arrival) =
+                self.link
+                    .send(&mut self.link_state, scheduler.now(), &mut self.rng)
+            {
+                scheduler.schedule_at(
+                    arrival,
+                    Event::Arrive {
+                        id,
+                        ma,
+                        pa,
+                        milestone,
+                    },
+                );
+            }
+        }
+    }
+
+    fn pick_recent(&mut self) -> InternedHash {
+        if self.recent.is_empty() {
+            0
+        } else {
+            self.recent[self.rng.gen_range(0, self.recent.len())]
+        }
+    }
+
+    // insert own transactions (with TSA)
+    fn broadcast(&mut self) {
+        if let Some((ma, pa)) = self.tangle.select_two_tips() {
+            let i = self.next_id();
+
+            self.tangle
+                .insert_own(i, Message::new((), MessageKind::Data), ma, pa);
+        }
+    }
+
+    // TODO: flag an existing transaction as milestone
+
+    // insert gossiped milestones (coordinator TSA: previous milestone in past cone)
+    fn milestone(&mut self) {
+        self.is_milestone = true;
+    }
+}
+
+// Logs the strategy and seed a tangle starts a run with.
+fn emit_started(tangle: &Tangle, seed: u64) {
+    let tsa_config = tangle.tsa_config();
+
+    tangle.events.emit(events::Event::Started {
+        tsa: tangle.tip_selector().name(),
+        ytrsi_delta: tsa_config.ytrsi_delta,
+        otrsi_delta: tsa_config.otrsi_delta,
+        below_max_depth: tsa_config.below_max_depth,
+        seed,
+    });
+}
@@ -0,0 +1,629 @@
+use super::{
+    emit_started, Intervals, Link, LinkState, Mode, Report, Request, Scheduler, Solidifier, Time,
+};
+use crate::events;
+use crate::stats::Stats;
+use crate::tangle::{InternedHash, Message, MessageKind, MilestoneIndex, Tangle};
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How the nodes of a network are connected; all links are bidirectional.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Topology {
+    /// Every node is connected to every other node.
+    FullMesh,
+    /// Every node is connected to its two neighbours on a ring.
+    Ring,
+    /// A ring, with random links added until every node has at least the given number of neighbours.
+    Random(usize),
+}
+
+impl FromStr for Topology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "full" => Ok(Self::FullMesh),
+            "ring" => Ok(Self::Ring),
+            _ => match s.strip_prefix("random:").map(|degree| degree.parse()) {
+                Some(Ok(degree)) => Ok(Self::Random(degree)),
+                _ => Err(format!("unknown topology: {}", s)),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FullMesh => write!(f, "full"),
+            Self::Ring => write!(f, "ring"),
+            Self::Random(degree) => write!(f, "random:{}", degree),
+        }
+    }
+}
+
+impl Topology {
+    /// The sorted neighbours of each of `n` nodes.
+    pub fn neighbours(&self, n: usize, rng: &mut StdRng) -> Vec<Vec<usize>> {
+        let mut neighbours = vec![Vec::new(); n];
+        let connect = |neighbours: &mut Vec<Vec<usize>>, a: usize, b: usize| {
+            if a != b && !neighbours[a].contains(&b) {
+                neighbours[a].push(b);
+                neighbours[b].push(a);
+            }
+        };
+
+        match self {
+            Self::FullMesh => {
+                for a in 0..n {
+                    for b in a + 1..n {
+                        connect(&mut neighbours, a, b);
+                    }
+                }
+            }
+            Self::Ring | Self::Random(_) => {
+                for a in 0..n {
+                    connect(&mut neighbours, a, (a + 1) % n);
+                }
+            }
+        }
+
+        if let Self::Random(degree) = self {
+            let degree = (*degree).min(n.saturating_sub(1));
+
+            for a in 0..n {
+                while neighbours[a].len() < degree {
+                    let candidates = (0..n)
+                        .filter(|b| *b != a && !neighbours[a].contains(b))
+                        .collect::<Vec<_>>();
+                    let b = *candidates
+                        .choose(rng)
+                        .expect("fewer candidates than degree");
+
+                    connect(&mut neighbours, a, b);
+                }
+            }
+        }
+
+        for neighbours in neighbours.iter_mut() {
+            neighbours.sort_unstable();
+        }
+
+        neighbours
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Event {
+    // a node issues a message with its own TSA
+    Broadcast(usize),
+    // the coordinator (node 0) issues a milestone
+    Milestone,
+    // a message arrives at a node (`to`) from one of its neighbours (`from`)
+    Deliver {
+        to: usize,
+        from: usize,
+        id: InternedHash,
+    },
+    // a node (`from`) asks one of its neighbours (`to`) for a missing parent or milestone
+    Request {
+        to: usize,
+        from: usize,
+        request: Request,
+    },
+    // the confidence of the unconfirmed vertices is sampled at every node
+    Confidence,
+}
+
+struct Node {
+    tangle: Tangle,
+    stats: Arc<Stats>,
+    neighbours: Vec<usize>,
+    solidifier: Solidifier,
+}
+
+// NOTE: a message is rebuilt from this at every node, as the tangle takes ownership of it
+#[derive(Clone, Copy)]
+struct Envelope {
+    ma: InternedHash,
+    pa: InternedHash,
+    milestone: Option<MilestoneIndex>,
+}
+
+/// Several tangles, each issuing messages with its own TSA and forwarding the messages it learns about to its
+/// neighbours. Node 0 also acts as the coordinator, that issues the milestones.
+pub struct Network {
+    nodes: Vec<Node>,
+    // the messages in flight, that some node doesn't know yet
+    messages: HashMap<InternedHash, Envelope>,
+    // the ids of the milestones the coordinator issued, by index
+    milestones: HashMap<MilestoneIndex, InternedHash>,
+    seed: u64,
+    rng: StdRng,
+    intervals: Intervals,
+    link: Link,
+    // the state of every direction of every link
+    links: HashMap<(usize, usize), LinkState>,
+    last_tx_id: u64,
+    ms_index: MilestoneIndex,
+    last_milestone: Option<InternedHash>,
+    // the confidence, at which a vertex counts as practically confirmed, and how often it's sampled
+    confidence_threshold: f64,
+    confidence_interval: Time,
+}
+
+impl Network {
+    /// Connects the tangles, which should all start from the same state, with links that all behave like `link`;
+    /// `intervals.gossip` is ignored, as the gossip is what the other nodes issue.
+    pub fn new(
+        tangles: Vec<Tangle>,
+        topology: Topology,
+        seed: u64,
+        intervals: Intervals,
+        link: Link,
+    ) -> Self {
+        // NOTE: every source of randomness is derived from the seed
+        let mut rng = StdRng::seed_from_u64(seed);
+        let tangles = tangles
+            .into_iter()
+            .map(|tangle| tangle.with_seed(rng.gen()))
+            .collect::<Vec<_>>();
+        let mut rng = StdRng::seed_from_u64(rng.gen());
+
+        let neighbours = topology.neighbours(tangles.len(), &mut rng);
+
+        let last_tx_id = tangles
+            .iter()
+            .flat_map(|tangle| {
+                let vertices = tangle.vertices.iter().map(|v| *v.key()).max();
+                let seps = tangle.seps.iter().map(|sep| *sep.key()).max();
+                vertices.into_iter().chain(seps)
+            })
+            .max()
+            .unwrap_or(0);
+        let ms_index = tangles
+            .iter()
+            .map(|tangle| tangle.lmi.load(Ordering::Relaxed))
+            .max()
+            .unwrap_or(0)
+            + 1;
+
+        let nodes = tangles
+            .into_iter()
+            .zip(neighbours)
+            .enumerate()
+            .map(|(i, (mut tangle, neighbours))| {
+                tangle.events.set_node(i);
+
+                let stats = Arc::new(Stats::default());
+                tangle.events.subscribe(Arc::clone(&stats) as _);
+
+                Node {
+                    tangle,
+                    stats,
+                    neighbours,
+                    solidifier: Solidifier::disabled(),
+                }
+            })
+            .collect();
+
+        Self {
+            nodes,
+            messages: HashMap::new(),
+            milestones: HashMap::new(),
+            seed,
+            rng,
+            intervals,
+            link,
+            links: HashMap::new(),
+            last_tx_id,
+            ms_index,
+            last_milestone: None,
+            confidence_threshold: 1.0,
+            confidence_interval: 0,
+        }
+    }
+
+    /// Lets every node request its missing parents from its neighbours; see `Solidifier::new`.
+    pub fn with_solidifier(mut self, timeout: Time, retries: u32) -> Self {
+        for node in self.nodes.iter_mut() {
+            node.solidifier = Solidifier::new(timeout, retries);
+        }
+        self
+    }
+
+    /// Samples the confidence at every node; see `Simulator::with_confidence`.
+    pub fn with_confidence(mut self, threshold: f64, interval: Time) -> Self {
+        self.confidence_threshold = threshold;
+        self.confidence_interval = interval;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn tangle(&self, node: usize) -> &Tangle {
+        &self.nodes[node].tangle
+    }
+
+    /// Runs the network for `duration` milliseconds of simulated time, and reports on every node.
+    pub fn run(&mut self, mode: Mode, duration: Time) -> Vec<Report> {
+        let mut scheduler = Scheduler::new();
+
+        if self.intervals.milestone > 0 && !self.nodes.is_empty() {
+            scheduler.schedule(self.intervals.milestone, Event::Milestone);
+        }
+
+        if self.confidence_interval > 0 {
+            scheduler.schedule(self.confidence_interval, Event::Confidence);
+        }
+
+        // NOTE: the nodes start at random offsets, so that they don't all issue at the same time
+        if self.intervals.broadcast > 0 {
+            for node in 0..self.nodes.len() {
+                let offset = self.rng.gen_range(1, self.intervals.broadcast + 1);
+                scheduler.schedule(offset, Event::Broadcast(node));
+            }
+        }
+
+        let start = Instant::now();
+
+        for node in &self.nodes {
+            emit_started(&node.tangle, self.seed);
+
+            node.stats
+                .sample_tips(scheduler.now(), node.tangle.num_tips());
+        }
+
+        while let Some(event) = scheduler.next_until(duration) {
+            if mode == Mode::RealTime {
+                let due = start + Duration::from_millis(scheduler.now());
+                let now = Instant::now();
+
+                if due > now {
+                    thread::sleep(due - now);
+                }
+            }
+
+            let node = match event {
+                Event::Broadcast(node) => node,
+                Event::Milestone | Event::Confidence => 0,
+                Event::Deliver { to, .. } | Event::Request { to, .. } => to,
+            };
+
+            self.nodes[node].tangle.events.set_time(scheduler.now());
+
+            match event {
+                Event::Broadcast(node) => {
+                    self.broadcast(&mut scheduler, node);
+                    scheduler.schedule(self.intervals.broadcast, event);
+                }
+                Event::Milestone => {
+                    self.milestone(&mut scheduler);
+                    scheduler.schedule(self.intervals.milestone, event);
+                }
+                Event::Deliver { to, from, id } => self.deliver(&mut scheduler, to, from, id),
+                Event::Request { to, from, request } => {
+                    self.answer(&mut scheduler, to, from, request)
+                }
+                Event::Confidence => {
+                    for node in &self.nodes {
+                        node.stats.sample_confidence(
+                            &node.tangle,
+                            scheduler.now(),
+                            self.confidence_threshold,
+                        );
+                    }
+                    scheduler.schedule(self.confidence_interval, event);
+                }
+            }
+
+            self.solidify(&mut scheduler, node);
+
+            self.nodes[node]
+                .stats
+                .sample_tips(scheduler.now(), self.nodes[node].tangle.num_tips());
+        }
+
+        for node in &self.nodes {
+            node.tangle.events.set_time(scheduler.now());
+            node.tangle.events.emit(events::Event::Finished);
+        }
+
+        let elapsed = start.elapsed().as_millis() as u64;
+
+        (0..self.nodes.len())
+            .map(|node| Report {
+                elapsed,
+                ..self.report(node, scheduler.now())
+            })
+            .collect()
+    }
+
+    pub fn report(&self, node: usize, simulated: Time) -> Report {
+        let tangle = &self.nodes[node].tangle;
+
+        Report {
+            node: Some(node),
+            tsa: tangle.tip_selector().name(),
+            tsa_config: tangle.tsa_config(),
+            seed: self.seed,
+            simulated,
+            elapsed: 0,
+            vertices: tangle.vertices.len(),
+            lmi: tangle.lmi.load(Ordering::Relaxed),
+            lsmi: tangle.lsmi.load(Ordering::Relaxed),
+            stats: self.nodes[node].stats.summary(tangle, simulated),
+        }
+    }
+
+    fn next_id(&mut self) -> u64 {
+        self.last_tx_id += 1;
+        self.last_tx_id
+    }
+
+    // a node issues a message with its own TSA, and sends it to its neighbours
+    fn broadcast(&mut self, scheduler: &mut Scheduler<Event>, node: usize) {
+        if let Some((ma, pa)) = self.nodes[node].tangle.select_two_tips() {
+            let id = self.next_id();
+
+            self.messages.insert(
+                id,
+                Envelope {
+                    ma,
+                    pa,
+                    milestone: None,
+                },
+            );
+            self.nodes[node]
+                .tangle
+                .insert_own(id, Message::new((), MessageKind::Data), ma, pa);
+
+            self.forward(scheduler, node, None, id);
+        }
+    }
+
+    // NOTE: the coordinator selects the tips of a milestone with the TSA of its node, and falls back to the previous
+    // milestone if there are none, so that the milestones keep coming
+    fn milestone(&mut self, scheduler: &mut Scheduler<Event>) {
+        let parents = self.nodes[0]
+            .tangle
+            .select_two_tips()
+            .or_else(|| self.last_milestone.map(|id| (id, id)));
+
+        if let Some((ma, pa)) = parents {
+            let id = self.next_id();
+            let index = self.ms_index;
+
+            self.ms_index += 1;
+            self.last_milestone = Some(id);
+            self.milestones.insert(index, id);
+            self.messages.insert(
+                id,
+                Envelope {
+                    ma,
+                    pa,
+                    milestone: Some(index),
+                },
+            );
+            self.nodes[0].tangle.insert_gossip(
+                id,
+                Message::new((), MessageKind::Milestone(index)),
+                ma,
+                pa,
+            );
+
+            self.forward(scheduler, 0, None, id);
+        }
+    }
+
+    fn deliver(
+        &mut self,
+        scheduler: &mut Scheduler<Event>,
+        to: usize,
+        from: usize,
+        id: InternedHash,
+    ) {
+        // NOTE: every message is forwarded only the first time it arrives, which stops the flooding
+        if self.nodes[to].tangle.is_known(&id) {
+            return;
+        }
+
+        let Envelope { ma, pa, milestone } = self.messages[&id];
+        let kind = match milestone {
+            Some(index) => MessageKind::Milestone(index),
+            None => MessageKind::Data,
+        };
+
+        self.nodes[to]
+            .tangle
+            .insert_gossip(id, Message::new((), kind), ma, pa);
+
+        self.forward(scheduler, to, Some(from), id);
+    }
+
+    // NOTE: a node asks its neighbours in turn, so that a retry goes to another neighbour
+    fn solidify(&mut self, scheduler: &mut Scheduler<Event>, node: usize) {
+        let Node {
+            tangle, solidifier, ..
+        } = &mut self.nodes[node];
+        let requests = solidifier.poll(tangle, scheduler.now());
+
+        for (request, attempt) in requests {
+            let neighbours = &self.nodes[node].neighbours;
+
+            if neighbours.is_empty() {
+                continue;
+            }
+
+            let to = neighbours[(attempt as usize - 1) % neighbours.len()];
+            let state = self.links.entry((node, to)).or_default();
+
+            if let Some(arrival) = self.link.send(state, scheduler.now(), &mut self.rng) {
+                scheduler.schedule_at(
+                    arrival,
+                    Event::Request {
+                        to,
+                        from: node,
+                        request,
+                    },
+                );
+            }
+        }
+    }
+
+    // NOTE: a node can only answer with what is still in its tangle, otherwise the request times out
+    fn answer(
+        &mut self,
+        scheduler: &mut Scheduler<Event>,
+        node: usize,
+        to: usize,
+        request: Request,
+    ) {
+        let id = match request {
+            Request::Parent(id) => id,
+            Request::Milestone(index) => match self.milestones.get(&index) {
+                Some(id) => *id,
+                None => return,
+            },
+        };
+
+        if !self.nodes[node].tangle.vertices.contains_key(&id) || !self.messages.contains_key(&id) {
+            return;
+        }
+
+        let state = self.links.entry((node, to)).or_default();
+
+        if let Some(arrival) = self.link.send(state, scheduler.now(), &mut self.rng) {
+            scheduler.schedule_at(arrival, Event::Deliver { to, from: node, id });
+        }
+    }
+
+    fn forward(
+        &mut self,
+        scheduler: &mut Scheduler<Event>,
+        node: usize,
+        from: Option<usize>,
+        id: InternedHash,
+    ) {
+        for i in 0..self.nodes[node].neighbours.len() {
+            let to = self.nodes[node].neighbours[i];
+
+            if Some(to) == from {
+                continue;
+            }
+
+            let state = self.links.entry((node, to)).or_default();
+
+            if let Some(arrival) = self.link.send(state, scheduler.now(), &mut self.rng) {
+                scheduler.schedule_at(arrival, Event::Deliver { to, from: node, id });
+            }
+        }
+
+        // NOTE: a message is only kept until every node knows it; a delivery still in flight then finds it known and
+        // doesn't need it anymore
+        if self.nodes.iter().all(|node| node.tangle.is_known(&id)) {
+            self.messages.remove(&id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::make_tangle_1_milestone;
+
+    #[test]
+    fn topologies() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        assert_eq!(
+            vec![vec![1, 2], vec![0, 2], vec![0, 1]],
+            Topology::FullMesh.neighbours(3, &mut rng)
+        );
+        assert_eq!(vec![1, 4], Topology::Ring.neighbours(5, &mut rng)[0]);
+        assert!(Topology::Random(3)
+            .neighbours(8, &mut rng)
+            .iter()
+            .all(|neighbours| neighbours.len() >= 3));
+        assert_eq!(Ok(Topology::Random(4)), "random:4".parse());
+    }
+
+    #[test]
+    fn nodes_converge() {
+        let tangles = (0..4).map(|_| make_tangle_1_milestone()).collect();
+        let intervals = Intervals {
+            gossip: 0,
+            broadcast: 1000,
+            milestone: 10_000,
+        };
+
+        let link = Link {
+            latency: 100,
+            ..Link::default()
+        };
+
+        let mut network = Network::new(tangles, Topology::Ring, 0, intervals, link);
+        let reports = network.run(Mode::Virtual, 60_000);
+
+        assert_eq!(4, reports.len());
+
+        for report in &reports {
+            assert!(report.stats.own > 0);
+            assert!(report.lsmi > 1);
+        }
+
+        // NOTE: every node knows every message, except for the ones still in flight at the end (a ring of 4 nodes
+        // has a diameter of 2 links, and every node issues a message per second)
+        for node in 0..network.len() {
+            let missed = network.last_tx_id as usize - network.tangle(node).vertices.len();
+
+            assert!(missed <= 4, "node {} missed {} messages", node, missed);
+        }
+
+        // NOTE: only the messages, that some node doesn't know yet, are kept
+        assert!(network.messages.len() <= 4);
+    }
+
+    #[test]
+    fn lossy_links_solidify() {
+        let tangles = (0..4).map(|_| make_tangle_1_milestone()).collect();
+        let intervals = Intervals {
+            gossip: 0,
+            broadcast: 1000,
+            milestone: 10_000,
+        };
+
+        let link = Link {
+            latency: 100,
+            drop: 0.05,
+            ..Link::default()
+        };
+
+        let mut network =
+            Network::new(tangles, Topology::Ring, 0, intervals, link).with_solidifier(500, 5);
+        let reports = network.run(Mode::Virtual, 60_000);
+
+        for report in &reports {
+            assert!(report.stats.requests > 0);
+            assert_eq!(0, report.stats.requests_failed);
+            // NOTE: only the parents of the messages still in flight can be missing at the end
+            assert!(report.stats.missing <= 4);
+            assert!(report.lsmi + 1 >= report.lmi);
+        }
+    }
+}
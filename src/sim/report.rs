@@ -0,0 +1,131 @@
+use super::Time;
+use crate::stats::Summary;
+use crate::tangle::{MilestoneIndex, TsaConfig};
+
+use std::fmt::{self, Write as _};
+use std::io::{self, Write};
+
+/// A summary of a finished run, together with the parameters it ran with.
+#[derive(Clone, Debug)]
+pub struct Report {
+    // the node of a network the report is about
+    pub node: Option<usize>,
+    pub tsa: &'static str,
+    pub tsa_config: TsaConfig,
+    pub seed: u64,
+    pub simulated: Time,
+    // the wall-clock time of the run in milliseconds; as it differs from run to run, it's left out of the CSV and
+    // JSON output
+    pub elapsed: u64,
+    pub vertices: usize,
+    pub lmi: MilestoneIndex,
+    pub lsmi: MilestoneIndex,
+    pub stats: Summary,
+}
+
+impl Report {
+    fn parameters(&self) -> Vec<(&'static str, String)> {
+        vec![
+            (
+                "node",
+                self.node.map_or(String::new(), |node| node.to_string()),
+            ),
+            ("tsa", self.tsa.to_string()),
+            ("ytrsi_delta", self.tsa_config.ytrsi_delta.to_string()),
+            ("otrsi_delta", self.tsa_config.otrsi_delta.to_string()),
+            (
+                "below_max_depth",
+                self.tsa_config.below_max_depth.to_string(),
+            ),
+            ("seed", self.seed.to_string()),
+            ("simulated", self.simulated.to_string()),
+            ("vertices", self.vertices.to_string()),
+            ("lmi", self.lmi.to_string()),
+            ("lsmi", self.lsmi.to_string()),
+        ]
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(node) = self.node {
+            write!(f, "node={} ", node)?;
+        }
+
+        writeln!(
+            f,
+            "tsa={} {} seed={} simulated={}ms elapsed={}ms vertices={} lmi={} lsmi={}",
+            self.tsa,
+            self.tsa_config,
+            self.seed,
+            self.simulated,
+            self.elapsed,
+            self.vertices,
+            self.lmi,
+            self.lsmi
+        )?;
+        write!(f, "{}", self.stats)
+    }
+}
+
+/// Writes one row per run, so that parameter sweeps end up in a single table.
+pub fn write_csv<W: Write>(reports: &[Report], mut out: W) -> io::Result<()> {
+    for (i, report) in reports.iter().enumerate() {
+        let row = report
+            .parameters()
+            .into_iter()
+            .chain(report.stats.metrics())
+            .collect::<Vec<_>>();
+
+        if i == 0 {
+            let header = row.iter().map(|(key, _)| *key).collect::<Vec<_>>();
+            writeln!(out, "{}", header.join(","))?;
+        }
+
+        let values = row.into_iter().map(|(_, value)| value).collect::<Vec<_>>();
+        writeln!(out, "{}", values.join(","))?;
+    }
+
+    Ok(())
+}
+
+/// Writes an array with one object per run, including the tip pool size over time.
+pub fn write_json<W: Write>(reports: &[Report], mut out: W) -> io::Result<()> {
+    let mut json = String::from("[");
+
+    for (i, report) in reports.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+
+        json.push('{');
+
+        for (key, value) in report.parameters() {
+            // NOTE: the strategy name is the only non-numeric value, and the node the only optional one
+            if key == "tsa" {
+                write!(json, "\"{}\":\"{}\",", key, value).expect("writing to a string");
+            } else if value.is_empty() {
+                write!(json, "\"{}\":null,", key).expect("writing to a string");
+            } else {
+                write!(json, "\"{}\":{},", key, value).expect("writing to a string");
+            }
+        }
+
+        for (key, value) in report.stats.metrics() {
+            write!(json, "\"{}\":{},", key, value).expect("writing to a string");
+        }
+
+        let tip_pool = report
+            .stats
+            .tip_pool
+            .iter()
+            .map(|(time, size)| format!("[{},{}]", time, size))
+            .collect::<Vec<_>>();
+
+        write!(json, "\"tip_pool\":[{}]}}", tip_pool.join(",")).expect("writing to a string");
+    }
+
+    json.push(']');
+
+    writeln!(out, "{}", json)
+}
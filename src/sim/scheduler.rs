@@ -0,0 +1,130 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+/// Virtual time in milliseconds since the start of the simulation.
+pub type Time = u64;
+
+struct Entry<E> {
+    time: Time,
+    seq: u64,
+    event: E,
+}
+
+// NOTE: events due at the same time are processed in the order they were scheduled
+impl<E> Ord for Entry<E> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.time, self.seq).cmp(&(other.time, other.seq))
+    }
+}
+
+impl<E> PartialOrd for Entry<E> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<E> PartialEq for Entry<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<E> Eq for Entry<E> {}
+
+/// A discrete-event scheduler driven by a virtual clock.
+pub struct Scheduler<E> {
+    now: Time,
+    seq: u64,
+    queue: BinaryHeap<Reverse<Entry<E>>>,
+}
+
+impl<E> Default for Scheduler<E> {
+    fn default() -> Self {
+        Self {
+            now: 0,
+            seq: 0,
+            queue: BinaryHeap::new(),
+        }
+    }
+}
+
+impl<E> Scheduler<E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The current virtual time.
+    pub fn now(&self) -> Time {
+        self.now
+    }
+
+    /// Schedules `event` to happen `delay` milliseconds from now.
+    pub fn schedule(&mut self, delay: Time, event: E) {
+        self.schedule_at(self.now + delay, event);
+    }
+
+    /// Schedules `event` to happen at `time`; events in the past happen immediately.
+    pub fn schedule_at(&mut self, time: Time, event: E) {
+        let time = time.max(self.now);
+
+        self.queue.push(Reverse(Entry {
+            time,
+            seq: self.seq,
+            event,
+        }));
+        self.seq += 1;
+    }
+
+    /// The time of the next event, if there is one.
+    pub fn peek_time(&self) -> Option<Time> {
+        self.queue.peek().map(|Reverse(entry)| entry.time)
+    }
+
+    /// Advances the clock to the next event due no later than `until`, and returns it.
+    pub fn next_until(&mut self, until: Time) -> Option<E> {
+        match self.peek_time() {
+            Some(time) if time <= until => {
+                let Reverse(entry) = self.queue.pop().unwrap();
+                self.now = entry.time;
+
+                Some(entry.event)
+            }
+            _ => {
+                self.now = self.now.max(until);
+
+                None
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_in_time_then_schedule_order() {
+        let mut scheduler = Scheduler::new();
+
+        scheduler.schedule(20, "c");
+        scheduler.schedule(10, "a");
+        scheduler.schedule(10, "b");
+        scheduler.schedule(30, "d");
+
+        assert_eq!(Some("a"), scheduler.next_until(25));
+        assert_eq!(10, scheduler.now());
+        assert_eq!(Some("b"), scheduler.next_until(25));
+        assert_eq!(Some("c"), scheduler.next_until(25));
+        assert_eq!(None, scheduler.next_until(25));
+        assert_eq!(25, scheduler.now());
+        assert_eq!(1, scheduler.len());
+    }
+}
@@ -0,0 +1,157 @@
+use super::Time;
+use crate::events::Event;
+use crate::tangle::{InternedHash, MilestoneIndex, Tangle};
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// What a tangle asks its peers for.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Request {
+    // a parent of a vertex, that can't become solid without it
+    Parent(InternedHash),
+    // a milestone, that the LSMI can't advance without
+    Milestone(MilestoneIndex),
+}
+
+struct Outstanding {
+    sent: Time,
+    attempts: u32,
+    failed: bool,
+}
+
+/// Requests the missing parents and milestones of a tangle, and requests them again if they don't arrive in time.
+pub struct Solidifier {
+    // how long to wait for an answer; 0 disables the solidifier
+    timeout: Time,
+    // how often a request for a parent is repeated before giving up
+    retries: u32,
+    // NOTE: ordered, so that the requests go out in a reproducible order
+    outstanding: BTreeMap<Request, Outstanding>,
+}
+
+impl Solidifier {
+    /// Waits `timeout` milliseconds for each missing parent, and asks again up to `retries` times.
+    pub fn new(timeout: Time, retries: u32) -> Self {
+        Self {
+            timeout,
+            retries,
+            outstanding: BTreeMap::new(),
+        }
+    }
+
+    pub fn disabled() -> Self {
+        Self::new(0, 0)
+    }
+
+    /// Returns what to request at `now`, together with the number of the attempt (starting at 1).
+    pub fn poll(&mut self, tangle: &Tangle, now: Time) -> Vec<(Request, u32)> {
+        if self.timeout == 0 {
+            return Vec::new();
+        }
+
+        let wanted = tangle
+            .missing
+            .iter()
+            .map(|entry| Request::Parent(*entry.key()))
+            .chain(
+                tangle
+                    .missing_milestones()
+                    .into_iter()
+                    .map(Request::Milestone),
+            )
+            .collect::<BTreeSet<_>>();
+
+        // NOTE: whatever isn't wanted anymore has arrived, one way or another
+        self.outstanding
+            .retain(|request, _| wanted.contains(request));
+
+        for request in wanted {
+            self.outstanding.entry(request).or_insert(Outstanding {
+                sent: now,
+                attempts: 0,
+                failed: false,
+            });
+        }
+
+        let mut requests = Vec::new();
+
+        for (request, outstanding) in self.outstanding.iter_mut() {
+            if outstanding.failed
+                || (outstanding.attempts > 0 && now < outstanding.sent + self.timeout)
+            {
+                continue;
+            }
+
+            outstanding.sent = now;
+            outstanding.attempts += 1;
+
+            match *request {
+                Request::Parent(id) => {
+                    if outstanding.attempts > self.retries + 1 {
+                        outstanding.failed = true;
+                        tangle.events.emit(Event::RequestFailed { id });
+                        continue;
+                    }
+
+                    tangle.events.emit(Event::Requested {
+                        id,
+                        attempt: outstanding.attempts,
+                    });
+                }
+                // NOTE: a missing milestone stalls the LSMI, so there's no giving up on it
+                Request::Milestone(index) => tangle.events.emit(Event::MilestoneRequested {
+                    index,
+                    attempt: outstanding.attempts,
+                }),
+            }
+
+            requests.push((*request, outstanding.attempts));
+        }
+
+        requests
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tangle::{Message, MessageKind};
+    use crate::utils::make_tangle_1_milestone;
+
+    #[test]
+    fn retries_and_gives_up() {
+        let tangle = make_tangle_1_milestone();
+        let mut solidifier = Solidifier::new(1000, 1);
+
+        tangle.insert_gossip(28, Message::default(), 27, 26);
+
+        assert_eq!(vec![(Request::Parent(27), 1)], solidifier.poll(&tangle, 0));
+        assert!(solidifier.poll(&tangle, 999).is_empty());
+        assert_eq!(
+            vec![(Request::Parent(27), 2)],
+            solidifier.poll(&tangle, 1000)
+        );
+        assert!(solidifier.poll(&tangle, 2000).is_empty());
+        assert!(solidifier.poll(&tangle, 5000).is_empty());
+
+        tangle.insert_gossip(29, Message::default(), 30, 26);
+
+        assert_eq!(
+            vec![(Request::Parent(30), 1)],
+            solidifier.poll(&tangle, 5000)
+        );
+
+        tangle.insert_gossip(30, Message::default(), 26, 26);
+
+        assert!(solidifier.poll(&tangle, 9000).is_empty());
+        assert!(tangle.is_solid(&29));
+
+        // NOTE: milestone 2 never arrived, so milestone 3 can't be confirmed
+        tangle.insert_gossip(31, Message::new((), MessageKind::Milestone(3)), 29, 26);
+
+        assert_eq!(
+            vec![(Request::Milestone(2), 1)],
+            solidifier.poll(&tangle, 9000)
+        );
+    }
+}
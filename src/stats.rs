@@ -0,0 +1,457 @@
+use crate::events::{Event, Level, Subscriber};
+use crate::sim::Time;
+use crate::tangle::{InternedHash, Score, Tangle};
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+/// Collects the metrics of a run from the events of its tangle.
+#[derive(Default)]
+pub struct Stats {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    // insertion time of every vertex inserted during the run
+    inserted: HashMap<InternedHash, Time>,
+    own: usize,
+    // time from insertion to confirmation of every confirmed vertex inserted during the run
+    latencies: Vec<Time>,
+    scores: [usize; 3],
+    tip_pool: Vec<(Time, usize)>,
+    requests: usize,
+    requests_failed: usize,
+    // time from insertion to solidification of every vertex, that wasn't solid right away
+    unsolid: Vec<Time>,
+    // the confidence, at which a vertex counts as practically confirmed, the vertices that got there, and how long
+    // each of them took; a confirmed vertex gets there at the latest when it is confirmed
+    threshold: Option<f64>,
+    confident: HashSet<InternedHash>,
+    confidence_latencies: Vec<Time>,
+    // every tip, that entered the semi-lazy pool, and what became of them
+    semi_lazy: HashSet<InternedHash>,
+    promoted: usize,
+    semi_lazy_dropped: usize,
+    semi_lazy_confirmed: usize,
+}
+
+impl Subscriber for Stats {
+    fn level(&self) -> Level {
+        Level::Trace
+    }
+
+    fn on_event(&self, time: Time, _node: Option<usize>, event: &Event) {
+        let mut inner = self.inner.lock().expect("poisoned stats");
+
+        match *event {
+            Event::Inserted { id, own, .. } => {
+                inner.inserted.insert(id, time);
+
+                if own {
+                    inner.own += 1;
+                }
+            }
+            Event::Confirmed { id, .. } => {
+                if let Some(inserted) = inner.inserted.get(&id).copied() {
+                    inner.latencies.push(time - inserted);
+
+                    if inner.confident.insert(id) {
+                        inner.confidence_latencies.push(time - inserted);
+                    }
+                }
+
+                if inner.semi_lazy.contains(&id) {
+                    inner.semi_lazy_confirmed += 1;
+                }
+            }
+            Event::SemiLazyAdded { id } => {
+                inner.semi_lazy.insert(id);
+            }
+            Event::Promoted { .. } => inner.promoted += 1,
+            // NOTE: a semi-lazy tip, that someone else approved, isn't lost
+            Event::SemiLazyRemoved { reason, .. } if reason != "approved" => {
+                inner.semi_lazy_dropped += 1
+            }
+            Event::Solidified { id } => {
+                if let Some(inserted) = inner.inserted.get(&id).copied() {
+                    if time > inserted {
+                        inner.unsolid.push(time - inserted);
+                    }
+                }
+            }
+            Event::ScoreComputed { score, .. } => inner.scores[score as usize] += 1,
+            Event::Requested { .. } | Event::MilestoneRequested { .. } => inner.requests += 1,
+            Event::RequestFailed { .. } => inner.requests_failed += 1,
+            _ => (),
+        }
+    }
+}
+
+impl Stats {
+    /// Records the size of the tip pool; only the last sample of a point in time, and only changes, are kept.
+    pub fn sample_tips(&self, time: Time, size: usize) {
+        let mut inner = self.inner.lock().expect("poisoned stats");
+
+        if let Some((last_time, _)) = inner.tip_pool.last() {
+            if *last_time == time {
+                inner.tip_pool.pop();
+            }
+        }
+
+        match inner.tip_pool.last() {
+            Some((_, last)) if *last == size => (),
+            _ => inner.tip_pool.push((time, size)),
+        }
+    }
+
+    /// Records which vertices have reached a confidence of at least `threshold` by now.
+    pub fn sample_confidence(&self, tangle: &Tangle, time: Time, threshold: f64) {
+        let confidences = tangle.confidences();
+        let mut inner = self.inner.lock().expect("poisoned stats");
+
+        inner.threshold = Some(threshold);
+
+        for (id, confidence) in confidences {
+            if confidence < threshold || inner.confident.contains(&id) {
+                continue;
+            }
+
+            if let Some(inserted) = inner.inserted.get(&id).copied() {
+                inner.confident.insert(id);
+                inner.confidence_latencies.push(time - inserted);
+            }
+        }
+    }
+
+    /// Summarizes the collected metrics; the tangle is needed for the ones that describe its final state.
+    pub fn summary(&self, tangle: &Tangle, end: Time) -> Summary {
+        let inner = self.inner.lock().expect("poisoned stats");
+
+        let mut latencies = inner.latencies.clone();
+        latencies.sort_unstable();
+
+        let mut confidence_latencies = inner.confidence_latencies.clone();
+        confidence_latencies.sort_unstable();
+
+        // NOTE: the tip pool size is weighted by how long it stayed that size
+        let mut tips_time_weighted = 0;
+        for (i, (time, size)) in inner.tip_pool.iter().enumerate() {
+            let until = inner.tip_pool.get(i + 1).map(|(t, _)| *t).unwrap_or(end);
+            tips_time_weighted += (until - time) as usize * size;
+        }
+        let start = inner.tip_pool.first().map(|(t, _)| *t).unwrap_or(end);
+        let tips_mean = if end > start {
+            tips_time_weighted as f64 / (end - start) as f64
+        } else {
+            inner.tip_pool.last().map(|(_, s)| *s).unwrap_or(0) as f64
+        };
+
+        // NOTE: an unconfirmed vertex, whose OTRSI is below max depth, can't be approved by a well-behaving node
+        // anymore, and will therefore never be confirmed
+        let lsmi = tangle.lsmi.load(Ordering::Relaxed);
+        let below_max_depth = tangle.tsa_config().below_max_depth;
+        let orphaned = inner
+            .inserted
+            .keys()
+            .filter_map(|id| tangle.get(id))
+            .filter(|metadata| metadata.confirmed.is_none())
+            .filter(|metadata| match metadata.otrsi {
+                Some(otrsi) => lsmi.saturating_sub(otrsi.0) > below_max_depth,
+                None => false,
+            })
+            .count();
+
+        let still_unsolid = inner
+            .inserted
+            .keys()
+            .filter(|id| tangle.vertices.contains_key(*id) && !tangle.is_solid(id))
+            .count();
+
+        Summary {
+            inserted: inner.inserted.len(),
+            own: inner.own,
+            confirmed: latencies.len(),
+            confirmation_rate: if inner.inserted.is_empty() {
+                0.0
+            } else {
+                latencies.len() as f64 / inner.inserted.len() as f64
+            },
+            latency_mean: mean(&latencies),
+            latency_p50: percentile(&latencies, 50),
+            latency_p90: percentile(&latencies, 90),
+            latency_p99: percentile(&latencies, 99),
+            confidence_threshold: inner.threshold.unwrap_or(1.0),
+            confident: confidence_latencies.len(),
+            confidence_mean: mean(&confidence_latencies),
+            confidence_p50: percentile(&confidence_latencies, 50),
+            confidence_p90: percentile(&confidence_latencies, 90),
+            tips_mean,
+            tips_max: inner.tip_pool.iter().map(|(_, s)| *s).max().unwrap_or(0),
+            lazy: inner.scores[Score::Lazy as usize],
+            semi_lazy: inner.scores[Score::SemiLazy as usize],
+            non_lazy: inner.scores[Score::NonLazy as usize],
+            orphaned,
+            missing: tangle.missing.len(),
+            requests: inner.requests,
+            requests_failed: inner.requests_failed,
+            waited: inner.unsolid.len(),
+            unsolid_mean: mean(&inner.unsolid),
+            unsolid_max: inner.unsolid.iter().copied().max().unwrap_or(0),
+            still_unsolid,
+            semi_lazy_tips: inner.semi_lazy.len(),
+            promoted: inner.promoted,
+            semi_lazy_confirmed: inner.semi_lazy_confirmed,
+            semi_lazy_dropped: inner.semi_lazy_dropped,
+            tip_pool: inner.tip_pool.clone(),
+        }
+    }
+}
+
+fn mean(times: &[Time]) -> f64 {
+    if times.is_empty() {
+        0.0
+    } else {
+        times.iter().sum::<Time>() as f64 / times.len() as f64
+    }
+}
+
+// NOTE: nearest-rank percentile of sorted times
+fn percentile(times: &[Time], p: usize) -> Time {
+    if times.is_empty() {
+        0
+    } else {
+        times[(p * times.len()).div_ceil(100).max(1) - 1]
+    }
+}
+
+/// The metrics of a run; latencies are in milliseconds from insertion to confirmation.
+#[derive(Clone, Debug, Default)]
+pub struct Summary {
+    pub inserted: usize,
+    pub own: usize,
+    pub confirmed: usize,
+    pub confirmation_rate: f64,
+    pub latency_mean: f64,
+    pub latency_p50: Time,
+    pub latency_p90: Time,
+    pub latency_p99: Time,
+    // how long it took until the vertices were practically confirmed, i.e. reached the confidence threshold
+    pub confidence_threshold: f64,
+    pub confident: usize,
+    pub confidence_mean: f64,
+    pub confidence_p50: Time,
+    pub confidence_p90: Time,
+    pub tips_mean: f64,
+    pub tips_max: usize,
+    pub lazy: usize,
+    pub semi_lazy: usize,
+    pub non_lazy: usize,
+    pub orphaned: usize,
+    pub missing: usize,
+    pub requests: usize,
+    pub requests_failed: usize,
+    // the number of vertices, that had to wait for missing parents, and how long they stayed unsolid
+    pub waited: usize,
+    pub unsolid_mean: f64,
+    pub unsolid_max: Time,
+    pub still_unsolid: usize,
+    // the number of tips, that were semi-lazy at some point, and how many of them were promoted, got confirmed, or
+    // were dropped from the semi-lazy pool
+    pub semi_lazy_tips: usize,
+    pub promoted: usize,
+    pub semi_lazy_confirmed: usize,
+    pub semi_lazy_dropped: usize,
+    pub tip_pool: Vec<(Time, usize)>,
+}
+
+impl Summary {
+    /// The scalar metrics by name, in the order they are reported and exported.
+    pub fn metrics(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("inserted", self.inserted.to_string()),
+            ("own", self.own.to_string()),
+            ("confirmed", self.confirmed.to_string()),
+            (
+                "confirmation_rate",
+                format!("{:.4}", self.confirmation_rate),
+            ),
+            ("latency_mean", format!("{:.1}", self.latency_mean)),
+            ("latency_p50", self.latency_p50.to_string()),
+            ("latency_p90", self.latency_p90.to_string()),
+            ("latency_p99", self.latency_p99.to_string()),
+            (
+                "confidence_threshold",
+                format!("{:.2}", self.confidence_threshold),
+            ),
+            ("confident", self.confident.to_string()),
+            ("confidence_mean", format!("{:.1}", self.confidence_mean)),
+            ("confidence_p50", self.confidence_p50.to_string()),
+            ("confidence_p90", self.confidence_p90.to_string()),
+            ("tips_mean", format!("{:.2}", self.tips_mean)),
+            ("tips_max", self.tips_max.to_string()),
+            ("lazy", self.lazy.to_string()),
+            ("semi_lazy", self.semi_lazy.to_string()),
+            ("non_lazy", self.non_lazy.to_string()),
+            ("orphaned", self.orphaned.to_string()),
+            ("missing", self.missing.to_string()),
+            ("requests", self.requests.to_string()),
+            ("requests_failed", self.requests_failed.to_string()),
+            ("waited", self.waited.to_string()),
+            ("unsolid_mean", format!("{:.1}", self.unsolid_mean)),
+            ("unsolid_max", self.unsolid_max.to_string()),
+            ("still_unsolid", self.still_unsolid.to_string()),
+            ("semi_lazy_tips", self.semi_lazy_tips.to_string()),
+            ("promoted", self.promoted.to_string()),
+            ("semi_lazy_confirmed", self.semi_lazy_confirmed.to_string()),
+            ("semi_lazy_dropped", self.semi_lazy_dropped.to_string()),
+        ]
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scored = (self.lazy + self.semi_lazy + self.non_lazy).max(1) as f64;
+
+        writeln!(
+            f,
+            "messages    : {} inserted ({} own), {} confirmed ({:.1}%)",
+            self.inserted,
+            self.own,
+            self.confirmed,
+            self.confirmation_rate * 100.0
+        )?;
+        writeln!(
+            f,
+            "confirmation: mean {:.0}ms, p50 {}ms, p90 {}ms, p99 {}ms",
+            self.latency_mean, self.latency_p50, self.latency_p90, self.latency_p99
+        )?;
+        writeln!(
+            f,
+            "confidence  : {} reached {:.0}%, after mean {:.0}ms, p50 {}ms, p90 {}ms",
+            self.confident,
+            self.confidence_threshold * 100.0,
+            self.confidence_mean,
+            self.confidence_p50,
+            self.confidence_p90
+        )?;
+        writeln!(
+            f,
+            "tip pool    : mean {:.2}, max {}",
+            self.tips_mean, self.tips_max
+        )?;
+        writeln!(
+            f,
+            "tip scores  : {:.1}% lazy, {:.1}% semi-lazy, {:.1}% non-lazy ({} scored)",
+            self.lazy as f64 / scored * 100.0,
+            self.semi_lazy as f64 / scored * 100.0,
+            self.non_lazy as f64 / scored * 100.0,
+            self.lazy + self.semi_lazy + self.non_lazy
+        )?;
+        writeln!(
+            f,
+            "semi-lazy   : {} tips, {} promoted, {} confirmed ({:.1}%), {} dropped",
+            self.semi_lazy_tips,
+            self.promoted,
+            self.semi_lazy_confirmed,
+            self.semi_lazy_confirmed as f64 / self.semi_lazy_tips.max(1) as f64 * 100.0,
+            self.semi_lazy_dropped
+        )?;
+        writeln!(
+            f,
+            "solidifier  : {} requests ({} failed), {} waited for parents (mean {:.0}ms, max {}ms)",
+            self.requests, self.requests_failed, self.waited, self.unsolid_mean, self.unsolid_max
+        )?;
+        write!(
+            f,
+            "leftovers   : {} orphaned, {} unsolid, {} missing parents outstanding",
+            self.orphaned, self.still_unsolid, self.missing
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::make_tangle_1_milestone;
+
+    #[test]
+    fn latencies_and_tip_pool() {
+        let tangle = make_tangle_1_milestone();
+        let stats = Stats::default();
+
+        for (time, id) in [(0, 100), (1000, 101), (2000, 102), (3000, 103)].iter() {
+            stats.on_event(
+                *time,
+                None,
+                &Event::Inserted {
+                    id: *id,
+                    ma: 0,
+                    pa: 0,
+                    own: *id == 101,
+                },
+            );
+        }
+        stats.on_event(5000, None, &Event::Confirmed { id: 100, index: 2 });
+        stats.on_event(5000, None, &Event::Confirmed { id: 101, index: 2 });
+        stats.on_event(5000, None, &Event::Confirmed { id: 1, index: 2 });
+        stats.on_event(0, None, &Event::Solidified { id: 100 });
+        stats.on_event(1500, None, &Event::Solidified { id: 101 });
+        stats.on_event(3000, None, &Event::Requested { id: 99, attempt: 1 });
+
+        stats.sample_tips(0, 4);
+        stats.sample_tips(500, 4);
+        stats.sample_tips(1000, 3);
+        stats.sample_tips(1000, 2);
+
+        let summary = stats.summary(&tangle, 2000);
+
+        assert_eq!(4, summary.inserted);
+        assert_eq!(1, summary.own);
+        assert_eq!(2, summary.confirmed);
+        assert_eq!(0.5, summary.confirmation_rate);
+        assert_eq!(4500.0, summary.latency_mean);
+        assert_eq!(4000, summary.latency_p50);
+        assert_eq!(5000, summary.latency_p99);
+        assert_eq!(vec![(0, 4), (1000, 2)], summary.tip_pool);
+        assert_eq!(3.0, summary.tips_mean);
+        assert_eq!(4, summary.tips_max);
+        assert_eq!(1, summary.requests);
+        assert_eq!(1, summary.waited);
+        assert_eq!(500, summary.unsolid_max);
+    }
+
+    #[test]
+    fn practical_confirmation() {
+        let tangle = make_tangle_1_milestone();
+        let stats = Stats::default();
+
+        for id in [19, 22].iter() {
+            stats.on_event(
+                0,
+                None,
+                &Event::Inserted {
+                    id: *id,
+                    ma: 0,
+                    pa: 0,
+                    own: false,
+                },
+            );
+        }
+
+        // NOTE: 19 is approved by 3 of the 4 tips, 22 only by 2 of them
+        stats.sample_confidence(&tangle, 1000, 0.7);
+        stats.sample_confidence(&tangle, 2000, 0.7);
+        stats.on_event(5000, None, &Event::Confirmed { id: 19, index: 2 });
+        stats.on_event(6000, None, &Event::Confirmed { id: 22, index: 2 });
+
+        let summary = stats.summary(&tangle, 6000);
+
+        assert_eq!(0.7, summary.confidence_threshold);
+        assert_eq!(2, summary.confident);
+        assert_eq!(3500.0, summary.confidence_mean);
+        assert_eq!(5500.0, summary.latency_mean);
+    }
+}
@@ -0,0 +1,127 @@
+use super::models::*;
+use super::Tangle;
+
+use std::fmt::Write;
+
+impl Tangle {
+    /// Renders the tangle in the Graphviz DOT language, with an edge from every vertex to its parents (`ma` solid,
+    /// `pa` dashed). Milestones are gold boxes, confirmed vertices blue, solid ones white and unsolid ones grey; tips
+    /// have a green border, solid entry points are green diamonds, and missing parents red dotted circles.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+
+        writeln!(dot, "digraph tangle {{").unwrap();
+        writeln!(dot, "    rankdir=RL;").unwrap();
+        writeln!(dot, "    node [shape=circle, style=filled, fontsize=10];").unwrap();
+
+        // NOTE: sorted, so that the same tangle always renders to the same output
+        let mut seps = self
+            .seps
+            .iter()
+            .map(|entry| (*entry.key(), *entry.value()))
+            .collect::<Vec<_>>();
+        seps.sort_unstable();
+
+        for (id, index) in seps {
+            writeln!(
+                dot,
+                "    {} [label=\"{}\\nsep {}\", shape=diamond, fillcolor=palegreen];",
+                id, id, index
+            )
+            .unwrap();
+        }
+
+        let mut missing = self
+            .missing
+            .iter()
+            .map(|entry| *entry.key())
+            .collect::<Vec<_>>();
+        missing.sort_unstable();
+
+        for id in missing {
+            writeln!(
+                dot,
+                "    {} [label=\"{}\\nmissing\", style=dotted, color=red];",
+                id, id
+            )
+            .unwrap();
+        }
+
+        let mut ids = self
+            .vertices
+            .iter()
+            .map(|entry| *entry.key())
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+
+        for id in ids {
+            let (parents, metadata, milestone) = match self.vertices.get(&id) {
+                Some(vertex) => (
+                    vertex.parents.clone(),
+                    vertex.metadata,
+                    match vertex.message.kind {
+                        MessageKind::Milestone(index) => Some(index),
+                        _ => None,
+                    },
+                ),
+                None => continue,
+            };
+
+            let mut label = id.to_string();
+            if let Some(index) = milestone {
+                write!(label, "\\nMS {}", index).unwrap();
+            }
+            match (metadata.otrsi, metadata.ytrsi) {
+                (Some(otrsi), Some(ytrsi)) => write!(label, "\\nO={} Y={}", otrsi.0, ytrsi.0),
+                _ => write!(label, "\\nO=- Y=-"),
+            }
+            .unwrap();
+            if let Some(index) = metadata.confirmed {
+                write!(label, "\\nconf {}", index).unwrap();
+            }
+
+            let mut attributes = format!("label=\"{}\"", label);
+            if milestone.is_some() {
+                attributes.push_str(", shape=box, fillcolor=gold");
+            } else if metadata.confirmed.is_some() {
+                attributes.push_str(", fillcolor=lightblue");
+            } else if metadata.solid {
+                attributes.push_str(", fillcolor=white");
+            } else {
+                attributes.push_str(", fillcolor=lightgrey, style=\"filled,dashed\"");
+            }
+            if self.tips.contains(&id) {
+                attributes.push_str(", color=green, penwidth=3");
+            }
+
+            writeln!(dot, "    {} [{}];", id, attributes).unwrap();
+            writeln!(dot, "    {} -> {};", id, parents.ma).unwrap();
+            writeln!(dot, "    {} -> {} [style=dashed];", id, parents.pa).unwrap();
+        }
+
+        writeln!(dot, "}}").unwrap();
+
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::make_tangle_1_milestone;
+
+    #[test]
+    fn one_milestone() {
+        let dot = make_tangle_1_milestone().to_dot();
+
+        assert!(dot.starts_with("digraph tangle {"));
+        assert!(dot.contains("    0 [label=\"0\\nsep 0\", shape=diamond, fillcolor=palegreen];"));
+        assert!(dot.contains(
+            "    12 [label=\"12\\nMS 1\\nO=1 Y=1\\nconf 1\", shape=box, fillcolor=gold];"
+        ));
+        assert!(dot.contains("    12 -> 8;\n    12 -> 11 [style=dashed];"));
+        assert!(dot.contains(
+            "    26 [label=\"26\\nO=1 Y=1\", fillcolor=white, color=green, penwidth=3];"
+        ));
+        assert_eq!(26 * 2, dot.matches(" -> ").count());
+    }
+}
@@ -0,0 +1,316 @@
+use super::models::*;
+use super::serialize::{parse, read_kind};
+use super::Tangle;
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::sync::atomic::Ordering;
+
+// NOTE: bump this whenever the format changes; only the current version is read
+const VERSION: u32 = 1;
+const HEADER: &str = "wurts-up fixture v";
+
+// The file format is line based, and meant to be written by hand; everything after a `#` is a comment:
+//
+//   wurts-up fixture v1
+//   sep 0 0
+//   message 8 5 6 milestone:1 solid=1 confirmed=1 otrsi=1 ytrsi=1
+//   message 10 7 8 data confirmed=-
+//   expect lmi=1 lsmi=1 tips=23,24 missing=0
+//
+// The messages arrive in the order they are listed. Everything after the kind of a message is what is expected of
+// it once all messages have arrived, and every expectation is optional.
+
+/// A scenario: the messages arriving at a tangle, and what is expected of the tangle afterwards.
+#[derive(Clone, Debug, Default)]
+pub struct Fixture {
+    pub seps: Vec<(InternedHash, MilestoneIndex)>,
+    pub messages: Vec<FixtureMessage>,
+    pub lmi: Option<MilestoneIndex>,
+    pub lsmi: Option<MilestoneIndex>,
+    pub tips: Option<Vec<InternedHash>>,
+    pub missing: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct FixtureMessage {
+    pub id: InternedHash,
+    pub ma: InternedHash,
+    pub pa: InternedHash,
+    pub kind: MessageKind,
+    pub solid: Option<bool>,
+    pub confirmed: Option<Confirmation>,
+    pub otrsi: Option<MilestoneIndex>,
+    pub ytrsi: Option<MilestoneIndex>,
+    pub cumulative_weight: Option<u64>,
+}
+
+impl Fixture {
+    pub fn read_from<R: BufRead>(input: R) -> Result<Self, String> {
+        let mut fixture = Fixture::default();
+        let mut lines = input.lines().enumerate();
+
+        let header = match lines.next() {
+            Some((_, line)) => line.map_err(|e| e.to_string())?,
+            None => return Err("empty fixture file".into()),
+        };
+        let version = header
+            .strip_prefix(HEADER)
+            .and_then(|version| version.trim().parse::<u32>().ok())
+            .ok_or_else(|| format!("not a fixture file: {}", header))?;
+
+        if version != VERSION {
+            return Err(format!("unsupported fixture file version: {}", version));
+        }
+
+        for (number, line) in lines {
+            let line = line.map_err(|e| e.to_string())?;
+            let line = line.split('#').next().unwrap_or("").trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            fixture
+                .read_line(line)
+                .map_err(|e| format!("line {}: {}", number + 1, e))?;
+        }
+
+        Ok(fixture)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+
+        Self::read_from(BufReader::new(file)).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    fn read_line(&mut self, line: &str) -> Result<(), String> {
+        let mut words = line.split_whitespace();
+
+        match words.next().unwrap_or("") {
+            "sep" => self.seps.push((parse(words.next())?, parse(words.next())?)),
+            "message" => {
+                let mut message = FixtureMessage {
+                    id: parse(words.next())?,
+                    ma: parse(words.next())?,
+                    pa: parse(words.next())?,
+                    kind: read_kind(words.next().unwrap_or(""))?,
+                    solid: None,
+                    confirmed: None,
+                    otrsi: None,
+                    ytrsi: None,
+                    cumulative_weight: None,
+                };
+
+                for (key, value) in key_values(words)? {
+                    match key {
+                        "solid" => message.solid = Some(parse::<u8>(Some(value))? != 0),
+                        "confirmed" if value == "-" => message.confirmed = Some(None),
+                        "confirmed" => message.confirmed = Some(Some(parse(Some(value))?)),
+                        "otrsi" => message.otrsi = Some(parse(Some(value))?),
+                        "ytrsi" => message.ytrsi = Some(parse(Some(value))?),
+                        "cumulative_weight" => {
+                            message.cumulative_weight = Some(parse(Some(value))?)
+                        }
+                        _ => return Err(format!("unknown message expectation: {}", key)),
+                    }
+                }
+
+                self.messages.push(message);
+            }
+            "expect" => {
+                for (key, value) in key_values(words)? {
+                    match key {
+                        "lmi" => self.lmi = Some(parse(Some(value))?),
+                        "lsmi" => self.lsmi = Some(parse(Some(value))?),
+                        "tips" if value == "-" => self.tips = Some(Vec::new()),
+                        "tips" => {
+                            let mut tips = value
+                                .split(',')
+                                .map(|tip| parse(Some(tip)))
+                                .collect::<Result<Vec<_>, _>>()?;
+                            tips.sort_unstable();
+
+                            self.tips = Some(tips);
+                        }
+                        "missing" => self.missing = Some(parse(Some(value))?),
+                        _ => return Err(format!("unknown expectation: {}", key)),
+                    }
+                }
+            }
+            other => return Err(format!("unknown entry: {}", other)),
+        }
+
+        Ok(())
+    }
+
+    /// Builds a fresh tangle, into which the messages arrive in the listed order.
+    pub fn build(&self) -> Tangle {
+        let tangle = Tangle::new();
+
+        for (id, index) in &self.seps {
+            tangle.add_solid_entrypoint(*id, *index);
+        }
+
+        for message in &self.messages {
+            tangle.insert_gossip(
+                message.id,
+                Message::new((), message.kind),
+                message.ma,
+                message.pa,
+            );
+        }
+
+        tangle
+    }
+
+    /// Checks the expectations against `tangle`, and lists every one that isn't met.
+    pub fn check(&self, tangle: &Tangle) -> Result<(), String> {
+        let mut failures = Vec::new();
+        let mut expect = |what: String, expected: String, found: String| {
+            if expected != found {
+                failures.push(format!("{}: expected {}, found {}", what, expected, found));
+            }
+        };
+        let show = |index: Option<MilestoneIndex>| index.map_or("-".into(), |i| i.to_string());
+
+        for message in &self.messages {
+            let metadata = match tangle.get(&message.id) {
+                Some(metadata) => metadata,
+                None => {
+                    expect(
+                        format!("message {}", message.id),
+                        "a vertex".into(),
+                        "nothing".into(),
+                    );
+                    continue;
+                }
+            };
+            let what = |field: &str| format!("message {} {}", message.id, field);
+
+            if let Some(solid) = message.solid {
+                expect(what("solid"), solid.to_string(), metadata.solid.to_string());
+            }
+            if let Some(confirmed) = message.confirmed {
+                expect(what("confirmed"), show(confirmed), show(metadata.confirmed));
+            }
+            if let Some(otrsi) = message.otrsi {
+                expect(
+                    what("otrsi"),
+                    show(Some(otrsi)),
+                    show(tangle.get_otrsi(&message.id)),
+                );
+            }
+            if let Some(ytrsi) = message.ytrsi {
+                expect(
+                    what("ytrsi"),
+                    show(Some(ytrsi)),
+                    show(tangle.get_ytrsi(&message.id)),
+                );
+            }
+            if let Some(cumulative_weight) = message.cumulative_weight {
+                expect(
+                    what("cumulative_weight"),
+                    cumulative_weight.to_string(),
+                    metadata.cumulative_weight.to_string(),
+                );
+            }
+        }
+
+        if let Some(lmi) = self.lmi {
+            expect(
+                "lmi".into(),
+                lmi.to_string(),
+                tangle.lmi.load(Ordering::Relaxed).to_string(),
+            );
+        }
+        if let Some(lsmi) = self.lsmi {
+            expect(
+                "lsmi".into(),
+                lsmi.to_string(),
+                tangle.lsmi.load(Ordering::Relaxed).to_string(),
+            );
+        }
+        if let Some(expected) = &self.tips {
+            let mut tips = tangle.tips.iter().map(|tip| *tip).collect::<Vec<_>>();
+            tips.sort_unstable();
+
+            expect(
+                "tips".into(),
+                format!("{:?}", expected),
+                format!("{:?}", tips),
+            );
+        }
+        if let Some(missing) = self.missing {
+            expect(
+                "missing parents".into(),
+                missing.to_string(),
+                tangle.missing.len().to_string(),
+            );
+        }
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures.join("\n"))
+        }
+    }
+}
+
+fn key_values<'a>(words: impl Iterator<Item = &'a str>) -> Result<Vec<(&'a str, &'a str)>, String> {
+    words
+        .map(|word| {
+            word.split_once('=')
+                .ok_or_else(|| format!("expected `key=value`: {}", word))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{replay, Order};
+
+    use std::fs;
+
+    // NOTE: every scenario in `fixtures/` has to hold, whatever order its messages arrive in
+    #[test]
+    fn fixtures_hold() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures");
+        let mut paths = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "fixture"))
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        assert!(!paths.is_empty());
+
+        for path in paths {
+            let fixture = Fixture::load(path.to_str().unwrap()).unwrap();
+            let tangle = fixture.build();
+
+            if let Err(e) = fixture.check(&tangle) {
+                panic!("{}:\n{}", path.display(), e);
+            }
+
+            for order in [Order::Reversed, Order::Shuffled(0), Order::Shuffled(1)].iter() {
+                if let Err(e) = fixture.check(&replay(&tangle, *order)) {
+                    panic!("{} ({:?}):\n{}", path.display(), order, e);
+                }
+            }
+        }
+
+        let wrong = Fixture::read_from(
+            "wurts-up fixture v1\nsep 0 0\nmessage 1 0 0 data confirmed=1 # never confirmed\nexpect lsmi=1"
+                .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            Err("message 1 confirmed: expected 1, found -\nlsmi: expected 1, found 0".into()),
+            wrong.check(&wrong.build())
+        );
+    }
+}
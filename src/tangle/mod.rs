@@ -1,15 +1,34 @@
+mod dot;
+mod fixture;
 mod models;
+mod pruning;
+mod semi_lazy;
+mod serialize;
+mod snapshot;
+pub mod storage;
+pub mod tsa;
+
+use crate::events::{Event, EventBus};
 
 use models::*;
+use semi_lazy::SemiLazyPool;
+use storage::{MemoryStorage, Record, Storage};
+use tsa::TipSelector;
 
-pub use models::{AtomicMilestoneIndex, Message, MessageKind};
+pub use fixture::Fixture;
+pub use models::{AtomicMilestoneIndex, InternedHash, Message, MessageKind, MilestoneIndex, Score};
+pub use semi_lazy::Promotion;
+pub use snapshot::Snapshot;
 
-use rand::Rng;
+use rand::{rngs::StdRng, SeedableRng};
 
 use std::cmp::{max, min};
+use std::collections::{BTreeSet, HashMap as StdHashMap, HashSet as StdHashSet};
+use std::fmt;
+use std::io;
 use std::ptr;
 use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
-use std::time::Instant;
+use std::sync::{Mutex, RwLock};
 
 use dashmap::{DashMap as HashMap, DashSet as HashSet};
 
@@ -17,12 +36,45 @@ const YTRSI_DELTA: u64 = 2; // C1
 const OTRSI_DELTA: u64 = 7; // C2
 const BELOW_MAX_DEPTH: u64 = 15; // M
 
+/// The parameters of the tip score from the Protocol RFC 0008.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TsaConfig {
+    pub ytrsi_delta: u64,     // C1
+    pub otrsi_delta: u64,     // C2
+    pub below_max_depth: u64, // M
+}
+
+impl fmt::Display for TsaConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "C1={} C2={} M={}",
+            self.ytrsi_delta, self.otrsi_delta, self.below_max_depth
+        )
+    }
+}
+
+impl Default for TsaConfig {
+    fn default() -> Self {
+        Self {
+            ytrsi_delta: YTRSI_DELTA,
+            otrsi_delta: OTRSI_DELTA,
+            below_max_depth: BELOW_MAX_DEPTH,
+        }
+    }
+}
+
 static TANGLE: AtomicPtr<Tangle> = AtomicPtr::new(ptr::null_mut());
 static INITIALIZED: AtomicBool = AtomicBool::new(false);
 
-pub fn init() {
-    if !INITIALIZED.compare_and_swap(false, true, Ordering::Relaxed) {
-        TANGLE.store(Box::into_raw(Tangle::default().into()), Ordering::Relaxed);
+// NOTE: the global instance is only a convenience for code that can't pass a `Tangle` around; prefer owning one
+// (or sharing it behind an `Arc`)
+pub fn init(tangle: Tangle) {
+    if INITIALIZED
+        .compare_exchange(false, true, Ordering::Relaxed, Ordering::Relaxed)
+        .is_ok()
+    {
+        TANGLE.store(Box::into_raw(tangle.into()), Ordering::Relaxed);
     } else {
         panic!("Tangle already initialized");
     }
@@ -37,13 +89,12 @@ pub fn tangle() -> &'static Tangle {
     }
 }
 
-#[derive(Default)]
 pub struct Tangle {
     // all vertices in the Tangle
     pub vertices: HashMap<InternedHash, Vertex>,
 
-    // missing parents; TODO: add confirmation info to it so, that it can be immediatedly set to confirmed if a
-    // milestone came in earlier
+    // missing parents; they can't be in the past cone of a confirmed milestone, as only solid milestones are
+    // confirmed
     pub missing: HashMap<InternedHash, Children>,
 
     // solid entry points and their corresponding milestone index; TODO: use `IndexId` type
@@ -51,11 +102,162 @@ pub struct Tangle {
 
     // vertices without children/approvers
     pub tips: HashSet<InternedHash>,
+    // tips, that were scored semi-lazy, and are only selected to promote them
+    semi_lazy: Mutex<SemiLazyPool>,
     pub lmi: AtomicMilestoneIndex,
     pub lsmi: AtomicMilestoneIndex,
+
+    // the milestones in memory by index; the ones above the LSMI wait for their past cone to become solid, or for
+    // the milestones before them to be confirmed
+    milestones: HashMap<MilestoneIndex, InternedHash>,
+
+    // subscribers to everything that happens in this tangle
+    pub events: EventBus,
+
+    // what's left of the vertices that were evicted from memory
+    storage: Box<dyn Storage>,
+
+    // the tip selection algorithm used for our own messages, its parameters, and the source of its randomness
+    tsa: Box<dyn TipSelector>,
+    tsa_config: RwLock<TsaConfig>,
+    rng: Mutex<StdRng>,
+}
+
+impl Default for Tangle {
+    fn default() -> Self {
+        Self {
+            vertices: HashMap::new(),
+            missing: HashMap::new(),
+            seps: HashMap::new(),
+            tips: HashSet::new(),
+            semi_lazy: Mutex::new(SemiLazyPool::default()),
+            lmi: AtomicMilestoneIndex::default(),
+            lsmi: AtomicMilestoneIndex::default(),
+            milestones: HashMap::new(),
+            events: EventBus::default(),
+            storage: Box::new(MemoryStorage::default()),
+            tsa: Box::new(tsa::Heuristic),
+            tsa_config: RwLock::new(TsaConfig::default()),
+            rng: Mutex::new(StdRng::seed_from_u64(0)),
+        }
+    }
 }
 
 impl Tangle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_tip_selector(mut self, tsa: Box<dyn TipSelector>) -> Self {
+        self.tsa = tsa;
+        self
+    }
+
+    pub fn with_tsa_config(self, tsa_config: TsaConfig) -> Self {
+        self.set_tsa_config(tsa_config);
+        self
+    }
+
+    pub fn tsa_config(&self) -> TsaConfig {
+        *self.tsa_config.read().expect("poisoned tsa config")
+    }
+
+    // NOTE: takes effect with the next tip score computation, so it can be changed while the simulation is running
+    pub fn set_tsa_config(&self, tsa_config: TsaConfig) {
+        *self.tsa_config.write().expect("poisoned tsa config") = tsa_config;
+    }
+
+    /// Selects semi-lazy tips according to `promotion`; unless they stay in the tip pool, up to `limit` (at least 1)
+    /// of them are kept apart from the other tips.
+    pub fn with_semi_lazy_pool(self, limit: usize, promotion: Promotion) -> Self {
+        {
+            let mut pool = self.semi_lazy.lock().expect("poisoned semi-lazy pool");
+
+            // NOTE: a loaded tangle might come with semi-lazy tips already
+            pool.limit = limit.max(1);
+            pool.promotion = promotion;
+            while pool.tips.len() > pool.limit {
+                pool.tips.pop_front();
+            }
+
+            if promotion == Promotion::Weighted {
+                for id in pool.tips.drain(..) {
+                    self.tips.insert(id);
+                }
+            }
+        }
+        self
+    }
+
+    pub(crate) fn promotion(&self) -> Promotion {
+        self.semi_lazy
+            .lock()
+            .expect("poisoned semi-lazy pool")
+            .promotion
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Mutex::new(StdRng::seed_from_u64(seed));
+        self
+    }
+
+    pub fn tip_selector(&self) -> &dyn TipSelector {
+        self.tsa.as_ref()
+    }
+
+    pub fn with_storage(mut self, storage: Box<dyn Storage>) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    pub fn storage(&self) -> &dyn Storage {
+        self.storage.as_ref()
+    }
+
+    /// Moves a vertex out of memory into the storage, which from then on answers solidity and RSI questions about
+    /// it. The vertex also leaves the tip pools and the milestone index. Returns `false` if there was no such vertex
+    /// in memory.
+    pub fn evict(&self, id: &InternedHash) -> io::Result<bool> {
+        let (parents, record, kind) = match self.vertices.get(id) {
+            Some(vertex) => (
+                vertex.parents.clone(),
+                Record::from(&vertex.metadata),
+                vertex.message.kind,
+            ),
+            None => return Ok(false),
+        };
+
+        // NOTE: store first, so that the vertex is never unknown to both
+        self.storage.insert(*id, record)?;
+
+        self.vertices.remove(id);
+
+        if self.tips.remove(id).is_some() {
+            self.events.emit(Event::TipRemoved { id: *id });
+        }
+
+        self.remove_semi_lazy(id, "evicted");
+
+        if let MessageKind::Milestone(index) = kind {
+            self.milestones
+                .remove_if(&index, |_, milestone| milestone == id);
+        }
+
+        for parent in &[parents.ma, parents.pa] {
+            if let Some(parent) = self.vertices.get(parent) {
+                parent.children.remove(id);
+            }
+
+            // NOTE: an evicted vertex doesn't wait for its missing parents anymore
+            self.missing.remove_if(parent, |_, children| {
+                children.remove(id);
+                children.is_empty()
+            });
+        }
+
+        Ok(true)
+    }
+
     pub fn insert_own(
         &self,
         id: InternedHash,
@@ -63,22 +265,32 @@ impl Tangle {
         ma: InternedHash,
         pa: InternedHash,
     ) {
-        let now = Instant::now();
+        if self.is_known(&id) {
+            self.events.emit(Event::Rejected {
+                id,
+                reason: "duplicate",
+            });
+            return;
+        }
 
         if message.kind != MessageKind::Data && message.kind != MessageKind::Value {
-            println!("Tried to insert unapproprite message type");
+            self.events.emit(Event::Rejected {
+                id,
+                reason: "inappropriate message type",
+            });
             return;
         }
 
-        self.tips.insert(id);
-
         // Here we check if parent-1 ("ma") exists; if it does then we update it with
         // the newly inserted vertex link
         if let Some(ma) = self.vertices.get_mut(&ma) {
             ma.children.insert(id);
         } else {
             if !self.seps.contains_key(&ma) && !self.check_db(&ma) {
-                println!("Tried to insert message with missing parent");
+                self.events.emit(Event::Rejected {
+                    id,
+                    reason: "missing parent",
+                });
                 return;
             }
         }
@@ -89,7 +301,10 @@ impl Tangle {
             pa.children.insert(id);
         } else {
             if !self.seps.contains_key(&pa) && !self.check_db(&pa) {
-                println!("Tried to insert message with missing parent");
+                self.events.emit(Event::Rejected {
+                    id,
+                    reason: "missing parent",
+                });
                 return;
             }
         }
@@ -98,19 +313,25 @@ impl Tangle {
         let vertex = Vertex {
             parents: Parents { ma, pa },
             message,
+            metadata: Metadata::new(1),
             ..Vertex::default()
         };
 
         // ... and insert it.
         self.vertices.insert(id, vertex);
+        self.add_weight(&id);
 
-        self.propagate_state(&id);
-
-        println!(
-            "[insert_own] Inserted own vertex with id={} in {:?}",
+        self.events.emit(Event::Inserted {
             id,
-            now.elapsed()
-        );
+            ma,
+            pa,
+            own: true,
+        });
+
+        self.tips.insert(id);
+        self.events.emit(Event::TipAdded { id });
+
+        self.propagate_state(&id);
     }
 
     pub fn insert_gossip(
@@ -120,14 +341,28 @@ impl Tangle {
         ma: InternedHash,
         pa: InternedHash,
     ) {
-        let now = Instant::now();
+        // NOTE: in a network the same message can arrive more than once, but inserting it again would corrupt its
+        // state
+        if self.is_known(&id) {
+            self.events.emit(Event::Rejected {
+                id,
+                reason: "duplicate",
+            });
+            return;
+        }
 
-        self.tips.remove(&ma);
-        self.tips.remove(&pa);
+        for parent in &[ma, pa] {
+            if self.tips.remove(parent).is_some() {
+                self.events.emit(Event::TipRemoved { id: *parent });
+            }
+
+            self.remove_semi_lazy(parent, "approved");
+        }
 
         let children = if !self.missing.contains_key(&id) {
             // no children yet; so *could* be a valid tip elligible for selecting
             self.tips.insert(id);
+            self.events.emit(Event::TipAdded { id });
 
             HashSet::new()
         } else {
@@ -141,7 +376,8 @@ impl Tangle {
         } else {
             if !self.seps.contains_key(&ma) && !self.check_db(&ma) {
                 // the parent is missing, but when it arrives we want to exclude it from the tip set
-                self.missing.entry(ma).or_insert(HashSet::new()).insert(id);
+                self.missing.entry(ma).or_default().insert(id);
+                self.events.emit(Event::MissingParent { id, parent: ma });
             }
         }
 
@@ -152,46 +388,44 @@ impl Tangle {
         } else {
             if !self.seps.contains_key(&pa) && !self.check_db(&pa) {
                 // the parent is missing, but when it arrives we want to exclude it from the tip set
-                self.missing.entry(pa).or_insert(HashSet::new()).insert(id);
+                self.missing.entry(pa).or_default().insert(id);
+                self.events.emit(Event::MissingParent { id, parent: pa });
             }
         }
 
         // Here we analyze the type of transaction; it's either a (string) message, or a milestone
         // (with an associated index)
-        let confirmed = match message.kind {
-            MessageKind::Milestone(index) => {
-                println!(
-                    "[insert_gsp] Milestone arrived with id={}, index={}",
-                    id, index
-                );
-
-                self.lmi.store(index, Ordering::Relaxed);
+        if let MessageKind::Milestone(index) = message.kind {
+            // NOTE: milestones can arrive out of order, so an older one must not lower the LMI
+            self.lmi.fetch_max(index, Ordering::Relaxed);
 
-                println!("[insert_gsp] LMI now at {}", index);
+            self.events.emit(Event::Milestone { id, index });
 
-                // NOTE: how to deal with the situation, that a milestone might not be solid?
-                let confirmed = self.confirm_recent_cone(&ma, &pa, index);
-
-                self.update_snapshot_indices(confirmed, index);
-
-                Some(index)
+            // NOTE: a milestone, whose past cone isn't solid yet, can't confirm it yet; one at or below the LSMI
+            // has been superseded already
+            if index > self.lsmi.load(Ordering::Relaxed) {
+                self.milestones.insert(index, id);
             }
-            _ => None,
-        };
+        }
 
         // Now we create a `Vertex`, that holds the transaction (Message or Milestone) ...
         let vertex = Vertex {
             parents: Parents { ma, pa },
             children,
             message,
-            metadata: Metadata {
-                confirmed,
-                ..Metadata::default()
-            },
+            metadata: Metadata::new(1),
         };
 
         // ... and insert it.
         self.vertices.insert(id, vertex);
+        self.add_weight(&id);
+
+        self.events.emit(Event::Inserted {
+            id,
+            ma,
+            pa,
+            own: false,
+        });
 
         // Here we propagate the state (solid, YTRSI, OTRSI) to its children (future cone)
         // `solid`: a child is solid, if its parents are solid (ma & pa)
@@ -199,26 +433,19 @@ impl Tangle {
         // `ytrsi`: the ytrsi of the child is the maximum of the ytrsi`s of its parents (max(ma.ytrsi, pa.ytrsi))
         self.propagate_state(&id);
 
-        println!(
-            "[insert_gsp] Inserted gossip vertex with id={} in {:?}",
-            id,
-            now.elapsed()
-        );
+        // Finally the vertex might have made waiting milestones solid
+        self.confirm_milestones();
     }
 
     // NOTE: there are 3 things being propagated/inherited: solid flag, otrsi, and ytrsi
     fn propagate_state(&self, root: &InternedHash) {
-        let now = Instant::now();
         let mut children = vec![*root];
 
-        //temp
-        let mut num_children = 0;
-
         while let Some(id) = children.pop() {
-            // NOTE: if it's already solid then we don't need to propagate a state change
+            // NOTE: if it's already solid then we don't need to propagate a state change; this happens, if a vertex
+            // approves both a vertex and one of its parents, as it is then reached twice
             if self.is_solid(&id) {
-                unreachable!("found already solid vertex during propagation to future cone");
-                // continue;
+                continue;
             }
 
             let parents = self
@@ -247,14 +474,7 @@ impl Tangle {
                 if let Some(mut vertex) = self.vertices.get_mut(&id) {
                     vertex.metadata.solid = true;
 
-                    match vertex.message.kind {
-                        MessageKind::Milestone(index) => {
-                            self.lsmi.store(index, Ordering::Relaxed);
-
-                            println!("[prop_state] LSMI now at {}", index);
-                        }
-                        _ => (),
-                    }
+                    self.events.emit(Event::Solidified { id });
 
                     vertex.metadata.otrsi = Some(otrsi);
                     vertex.metadata.ytrsi = Some(ytrsi);
@@ -265,46 +485,137 @@ impl Tangle {
                     // );
 
                     // maybe we can propagate state even further
-                    for child in vertex.children.iter() {
-                        children.push(*child);
-                    }
+                    // NOTE: sorted, so that events are emitted in a reproducible order
+                    let mut next = vertex
+                        .children
+                        .iter()
+                        .map(|child| *child)
+                        .collect::<Vec<_>>();
+                    next.sort_unstable_by(|a, b| b.cmp(a));
+
+                    children.extend(next);
+                }
+            }
+        }
+    }
+
+    // NOTE: the past cone of a confirmed vertex is confirmed, so everything between a new vertex and an unconfirmed
+    // one is unconfirmed as well; this keeps the cumulative weights of unconfirmed vertices exact, without having to
+    // walk the whole history for every new vertex
+    fn add_weight(&self, id: &InternedHash) {
+        let ancestors = self.unconfirmed_past_cone(id, None);
+        let weight = self
+            .vertices
+            .get(id)
+            .map_or(0, |vertex| vertex.metadata.weight);
+
+        for ancestor in &ancestors {
+            if let Some(mut vertex) = self.vertices.get_mut(ancestor) {
+                vertex.metadata.cumulative_weight += weight;
+            }
+        }
 
-                    num_children += vertex.children.len();
+        // NOTE: the children, that arrived before the vertex, now approve its ancestors too, unless they already did
+        // through another path
+        let mut late = Vec::new();
+        let mut visited = StdHashSet::new();
+        let mut next = self.vertices.get(id).map_or(Vec::new(), |vertex| {
+            vertex.children.iter().map(|child| *child).collect()
+        });
+
+        while let Some(child) = next.pop() {
+            if let Some(vertex) = self.vertices.get(&child) {
+                if visited.insert(child) {
+                    late.push((child, vertex.metadata.weight));
+                    next.extend(vertex.children.iter().map(|child| *child));
+                }
+            }
+        }
+
+        if let Some(mut vertex) = self.vertices.get_mut(id) {
+            vertex.metadata.cumulative_weight += late.iter().map(|(_, weight)| weight).sum::<u64>();
+        }
+
+        for (child, weight) in late {
+            let approved = self.unconfirmed_past_cone(&child, Some(id));
+
+            for ancestor in ancestors.difference(&approved) {
+                if let Some(mut vertex) = self.vertices.get_mut(ancestor) {
+                    vertex.metadata.cumulative_weight += weight;
+                }
+            }
+        }
+    }
+
+    // The unconfirmed vertices, that `id` approves directly or indirectly, without going through `skip`.
+    fn unconfirmed_past_cone(
+        &self,
+        id: &InternedHash,
+        skip: Option<&InternedHash>,
+    ) -> StdHashSet<InternedHash> {
+        let mut cone = StdHashSet::new();
+        let mut next = vec![*id];
+
+        while let Some(id) = next.pop() {
+            let parents = match self.vertices.get(&id) {
+                Some(vertex) => [vertex.parents.ma, vertex.parents.pa],
+                None => continue,
+            };
+
+            for parent in parents.iter() {
+                if Some(parent) == skip || cone.contains(parent) {
+                    continue;
+                }
+
+                if let Some(vertex) = self.vertices.get(parent) {
+                    if vertex.metadata.confirmed.is_none() {
+                        cone.insert(*parent);
+                        next.push(*parent);
+                    }
                 }
             }
         }
 
-        println!(
-            "[prop_state] Propagated state to vertex {} and its {} children in {:?}",
-            root,
-            num_children,
-            now.elapsed()
-        );
+        cone
     }
 
     // TODO: barrier?
 
-    // NOTE: this method confirms what it has in its past-cone whether it's solid or not
+    // NOTE: milestones are confirmed in index order, and only once they are solid, so that the LSMI never skips one
+    fn confirm_milestones(&self) {
+        loop {
+            let index = self.lsmi.load(Ordering::Relaxed) + 1;
+            let id = match self.milestones.get(&index).map(|id| *id) {
+                Some(id) if self.is_solid(&id) => id,
+                _ => break,
+            };
+
+            let confirmed = self.confirm_recent_cone(&id, index);
+
+            self.update_snapshot_indices(confirmed);
+
+            self.lsmi.store(index, Ordering::Relaxed);
+
+            self.events.emit(Event::SolidMilestone { index });
+        }
+    }
+
+    // NOTE: this method is only called for solid milestones, so their whole past-cone is known
     fn confirm_recent_cone(
         &self,
-        ma: &InternedHash,
-        pa: &InternedHash,
+        milestone: &InternedHash,
         index: MilestoneIndex,
     ) -> Vec<InternedHash> {
-        let now = Instant::now();
-        let mut visited = vec![*ma, *pa];
+        let mut visited = vec![*milestone];
         let mut confirmed = Vec::new();
 
         while let Some(id) = visited.pop() {
             if let Some(mut vertex) = self.vertices.get_mut(&id) {
                 if vertex.metadata.confirmed.is_none() {
-                    // println!(
-                    //     "[confirm   ] Confirmed vertex with id={} (ms_index={})",
-                    //     id, index
-                    // );
-
                     vertex.metadata.confirmed = Some(index);
 
+                    self.events.emit(Event::Confirmed { id, index });
+
                     // NOTE: Setting otrsi and ytrsi for  confirmed vertices - I think - prevents some branching,
                     // if the tip directly attaches to it
                     // NOTE: the confirmed vertex now points to itself with its otrsi and ytrsi (as it has become a root transaction)
@@ -319,87 +630,64 @@ impl Tangle {
                     visited.push(vertex.parents.pa);
                 }
             } else {
-                if !self.is_sep(&id) {
-                    todo!("[confirm   ] missing vertex: {}", id);
-                }
+                // NOTE: what isn't in memory anymore is a solid entry point, or was evicted, and is left as it is
             }
         }
 
-        println!(
-            "[confirm   ] Confirmed {} transactions in {:?}",
-            confirmed.len(),
-            now.elapsed()
-        );
-
         confirmed
     }
 
     // NOTE: so once a milestone comes in we have to walk the future cones of the root transactions and update their
-    // OTRSI and YTRSI
-    fn update_snapshot_indices(&self, mut confirmed: Vec<InternedHash>, index: MilestoneIndex) {
-        let now = Instant::now();
-        let mut children = Vec::new();
-        let mut updated = std::collections::HashSet::new();
-
-        while let Some(id) = confirmed.pop() {
-            children.clear();
-
-            // NOTE: Rust borrow rules force us to first create a children vec
-            let (otrsi, ytrsi) = if let Some(vertex) = self.vertices.get(&id) {
-                for child in vertex.children.iter() {
-                    children.push(*child);
+    // OTRSI and YTRSI; they are recomputed from the parents, as the parent a vertex got them from before isn't
+    // necessarily the one it gets them from now
+    fn update_snapshot_indices(&self, confirmed: Vec<InternedHash>) {
+        // NOTE: ordered, so that the update happens in a reproducible order, and mostly parents before children, so
+        // that a vertex rarely needs to be updated twice
+        let mut children = BTreeSet::new();
+
+        for id in &confirmed {
+            if let Some(vertex) = self.vertices.get(id) {
+                children.extend(vertex.children.iter().map(|child| *child));
+            }
+        }
+
+        while let Some(id) = children.pop_first() {
+            let parents = match self.vertices.get(&id) {
+                // NOTE: we can ignore already confirmed vertices, and the ones that don't have RSIs yet; they get
+                // theirs once they become solid
+                Some(vertex) if vertex.metadata.confirmed.is_none() && vertex.metadata.solid => {
+                    (vertex.parents.ma, vertex.parents.pa)
                 }
-                (
-                    vertex.metadata.otrsi.unwrap().0,
-                    vertex.metadata.ytrsi.unwrap().0,
-                )
-            } else {
-                panic!("[update rsi] Vertex not found");
+                _ => continue,
             };
 
-            for child in &children {
-                if let Some(mut vertex2) = self.vertices.get_mut(&child) {
-                    if vertex2.metadata.confirmed.is_some() {
-                        // NOTE: we can ignore already confirmed vertices
-                        // println!("[update rsi] No update required: {}", child);
-                        continue;
-                    }
-
-                    if let Some(index_id) = vertex2.metadata.otrsi {
-                        if index_id.1 == id {
-                            // println!(
-                            //     "[update rsi] Updating otrsi={} in {} from {}",
-                            //     otrsi, child, id
-                            // );
-
-                            //index_id = IndexId(otrsi, id);
-                            vertex2.metadata.otrsi.replace(IndexId(otrsi, id));
-                        }
-                    }
-
-                    if let Some(index_id) = vertex2.metadata.ytrsi {
-                        if index_id.1 == id {
-                            // println!(
-                            //     "[update rsi] Updating ytrsi={} in {} from {}",
-                            //     ytrsi, child, id
-                            // );
+            let (ma, pa) = parents;
+            let (otrsi, ytrsi) = match (
+                self.get_otrsi(&ma),
+                self.get_otrsi(&pa),
+                self.get_ytrsi(&ma),
+                self.get_ytrsi(&pa),
+            ) {
+                (Some(ma_otrsi), Some(pa_otrsi), Some(ma_ytrsi), Some(pa_ytrsi)) => (
+                    min(IndexId(ma_otrsi, ma), IndexId(pa_otrsi, pa)),
+                    max(IndexId(ma_ytrsi, ma), IndexId(pa_ytrsi, pa)),
+                ),
+                _ => continue,
+            };
 
-                            vertex2.metadata.ytrsi.replace(IndexId(ytrsi, id));
-                        }
-                    }
+            if let Some(mut vertex) = self.vertices.get_mut(&id) {
+                // NOTE: the children only read the indices, so only a changed index needs to be passed on to them
+                let changed = vertex.metadata.otrsi.map(|index_id| index_id.0) != Some(otrsi.0)
+                    || vertex.metadata.ytrsi.map(|index_id| index_id.0) != Some(ytrsi.0);
 
-                    if !updated.contains(child) {
-                        //println!("[update rsi] Proceding with {}", child);
+                vertex.metadata.otrsi = Some(otrsi);
+                vertex.metadata.ytrsi = Some(ytrsi);
 
-                        confirmed.push(*child);
-                    }
+                if changed {
+                    children.extend(vertex.children.iter().map(|child| *child));
                 }
             }
-
-            updated.insert(id);
         }
-
-        println!("[update rsi] Updated RSI values in {:?}", now.elapsed());
     }
 
     // Allows us to define certain `InternedHash`s as solid entry points.
@@ -408,45 +696,189 @@ impl Tangle {
     }
 
     pub fn is_solid(&self, id: &InternedHash) -> bool {
-        if let Some(vertex) = self.vertices.get(&id) {
+        if let Some(vertex) = self.vertices.get(id) {
             vertex.metadata.solid
         } else {
-            self.is_sep(id) || self.check_db(id)
+            self.is_sep(id) || self.stored(id).is_some_and(|record| record.solid)
         }
     }
 
+    /// Whether the vertex is in memory, a solid entry point, or in the storage.
+    pub fn is_known(&self, id: &InternedHash) -> bool {
+        self.vertices.contains_key(id) || self.is_sep(id) || self.check_db(id)
+    }
+
     pub fn is_sep(&self, id: &InternedHash) -> bool {
         self.seps.contains_key(id)
     }
 
     pub fn is_milestone(&self, id: &InternedHash) -> bool {
-        if let Some(vertex) = self.vertices.get(&id) {
+        if let Some(vertex) = self.vertices.get(id) {
             vertex.message.kind.is_milestone()
         } else {
             false
         }
     }
 
+    /// The id of the milestone with the given index, if it is still in memory.
+    pub fn milestone(&self, index: MilestoneIndex) -> Option<InternedHash> {
+        self.milestones
+            .get(&index)
+            .map(|id| *id)
+            .filter(|id| self.vertices.contains_key(id))
+    }
+
+    /// The weight of a vertex plus the weights of all vertices, that directly or indirectly approve it; for a
+    /// confirmed vertex it's the cumulative weight it had when it was confirmed.
+    pub fn cumulative_weight(&self, id: &InternedHash) -> u64 {
+        self.vertices
+            .get(id)
+            .map_or(0, |vertex| vertex.metadata.cumulative_weight)
+    }
+
+    /// How likely a vertex is to be confirmed: 1 once it is confirmed, and before that the fraction of the eligible
+    /// tips, that approve it directly or indirectly (or are the vertex itself).
+    pub fn confidence(&self, id: &InternedHash) -> Option<f64> {
+        let metadata = self.get(id)?;
+
+        if metadata.confirmed.is_some() {
+            return Some(1.0);
+        }
+
+        let tips = self.eligible_tips();
+
+        if tips.is_empty() {
+            return Some(0.0);
+        }
+
+        let mut visited = StdHashSet::new();
+        let mut next = vec![*id];
+        let mut approving = 0;
+
+        while let Some(id) = next.pop() {
+            if let Some(vertex) = self.vertices.get(&id) {
+                if visited.insert(id) {
+                    if tips.contains(&id) {
+                        approving += 1;
+                    }
+                    next.extend(vertex.children.iter().map(|child| *child));
+                }
+            }
+        }
+
+        Some(approving as f64 / tips.len() as f64)
+    }
+
+    /// The confidence of every unconfirmed vertex, that is approved by at least one eligible tip; all at once, as this
+    /// is much cheaper than asking for each of them.
+    pub fn confidences(&self) -> StdHashMap<InternedHash, f64> {
+        let tips = self.eligible_tips();
+        let mut approving = StdHashMap::new();
+
+        for tip in &tips {
+            if self.confirmed(tip) == Some(true) {
+                continue;
+            }
+
+            *approving.entry(*tip).or_insert(0) += 1;
+
+            for id in self.unconfirmed_past_cone(tip, None) {
+                *approving.entry(id).or_insert(0) += 1;
+            }
+        }
+
+        approving
+            .into_iter()
+            .map(|(id, count)| (id, count as f64 / tips.len() as f64))
+            .collect()
+    }
+
+    // NOTE: the solid tips above max depth are the ones a well-behaving TSA can still select; the others would
+    // make the confidence depend on how quickly a TSA cleans up its tip pool
+    fn eligible_tips(&self) -> StdHashSet<InternedHash> {
+        let lsmi = self.lsmi.load(Ordering::Relaxed);
+        let below_max_depth = self.tsa_config().below_max_depth;
+
+        self.tips
+            .iter()
+            .map(|tip| *tip)
+            .filter(|tip| match self.get_otrsi(tip) {
+                Some(otrsi) => self.is_solid(tip) && lsmi.saturating_sub(otrsi) <= below_max_depth,
+                None => false,
+            })
+            .collect()
+    }
+
+    // Sums up the weights of the future cone of `id` from scratch, as far as it shares the confirmation of `id`: for
+    // an unconfirmed vertex that's its whole future cone, and for a confirmed one the approvers, that were certainly
+    // there when it got confirmed.
+    pub(super) fn future_cone_weight(&self, id: &InternedHash) -> u64 {
+        let confirmed = match self.vertices.get(id) {
+            Some(vertex) => vertex.metadata.confirmed,
+            None => return 0,
+        };
+
+        let mut visited = StdHashSet::new();
+        let mut next = vec![*id];
+        let mut weight = 0;
+
+        while let Some(id) = next.pop() {
+            if let Some(vertex) = self.vertices.get(&id) {
+                if vertex.metadata.confirmed == confirmed && visited.insert(id) {
+                    weight += vertex.metadata.weight;
+                    next.extend(vertex.children.iter().map(|child| *child));
+                }
+            }
+        }
+
+        weight
+    }
+
+    /// The indices of the milestones, that are needed to advance the LSMI to the LMI, but haven't arrived yet.
+    pub fn missing_milestones(&self) -> Vec<MilestoneIndex> {
+        let lsmi = self.lsmi.load(Ordering::Relaxed);
+        let lmi = self.lmi.load(Ordering::Relaxed);
+
+        (lsmi + 1..lmi)
+            .filter(|index| !self.milestones.contains_key(index))
+            .collect()
+    }
+
     pub fn get_otrsi(&self, id: &InternedHash) -> Option<MilestoneIndex> {
-        if let Some(vertex) = self.vertices.get(&id) {
+        if let Some(vertex) = self.vertices.get(id) {
             vertex.metadata.otrsi.map(|index_id| index_id.0)
+        } else if let Some(index) = self.seps.get(id) {
+            Some(*index)
         } else {
-            self.seps.get(id).map(|index| *index)
+            self.stored(id).and_then(|record| record.otrsi)
         }
     }
 
     pub fn get_ytrsi(&self, id: &InternedHash) -> Option<MilestoneIndex> {
-        if let Some(vertex) = self.vertices.get(&id) {
+        if let Some(vertex) = self.vertices.get(id) {
             vertex.metadata.ytrsi.map(|index_id| index_id.0)
+        } else if let Some(index) = self.seps.get(id) {
+            Some(*index)
         } else {
-            self.seps.get(id).map(|index| *index)
+            self.stored(id).and_then(|record| record.ytrsi)
         }
     }
 
     // Checks wether the id (hash?) is in the db
-    fn check_db(&self, _id: &InternedHash) -> bool {
+    fn check_db(&self, id: &InternedHash) -> bool {
         // NOTE: the InternedHash type is not used in the db, but the (slower) transaction hash instead
-        false
+        self.stored(id).is_some()
+    }
+
+    // NOTE: a storage, that fails to read, can't stop the simulation; its vertices are treated as unknown instead
+    fn stored(&self, id: &InternedHash) -> Option<Record> {
+        match self.storage.get(id) {
+            Ok(record) => record,
+            Err(_) => {
+                self.events.emit(Event::StorageFailed { id: *id });
+                None
+            }
+        }
     }
 
     pub fn confirmed(&self, id: &InternedHash) -> Option<bool> {
@@ -456,17 +888,17 @@ impl Tangle {
     }
 
     pub fn get(&self, id: &InternedHash) -> Option<Metadata> {
-        self.vertices.get(id).map(|r| r.value().metadata.clone())
+        self.vertices.get(id).map(|r| r.value().metadata)
     }
 
     pub fn num_tips(&self) -> usize {
         self.tips.len()
     }
 
-    // TODO: add `select_two_tips` method
+    /// Selects the parents of an own message; the second one promotes a semi-lazy tip, if the policy allows it.
     pub fn select_two_tips(&self) -> Option<(InternedHash, InternedHash)> {
         if let Some(tip1) = self.select_tip() {
-            if let Some(tip2) = self.select_tip() {
+            if let Some(tip2) = self.promote().or_else(|| self.select_tip()) {
                 return Some((tip1, tip2));
             }
         }
@@ -474,151 +906,184 @@ impl Tangle {
         None
     }
 
-    /// Updates tip score, and performs the tip selection algorithm (TSA).
+    /// Performs the tip selection algorithm (TSA) this tangle was configured with.
     pub fn select_tip(&self) -> Option<InternedHash> {
-        let now = Instant::now();
+        let mut rng = self.rng.lock().expect("poisoned rng");
 
-        // From all the tips create a subset "solid tips"
-        let mut valid_tips = Vec::with_capacity(self.tips.len());
-        let mut score_sum = 0_isize;
-        let mut remove_list = Vec::new();
+        let selected = self.tsa.select_tip(self, &mut rng);
 
-        if self.tips.is_empty() {
-            return None;
+        match selected {
+            Some(id) => self.events.emit(Event::TipSelected { id }),
+            None => self.events.emit(Event::NoTipSelected),
         }
 
-        for id in self.tips.iter() {
-            if let Some(tip) = self.vertices.get(&id) {
-                let otrsi = tip.metadata.otrsi.expect("").0;
-                let ytrsi = tip.metadata.ytrsi.unwrap().0;
-
-                let score = self.get_tip_score(&id, otrsi, ytrsi) as isize;
-
-                // NOTE: only non- and semi-lazy tips are considered for selection
-                // TODO: think about the `solid` condition: what if a tip later becomes solid?
-                if !tip.metadata.solid || tip.metadata.selected >= 2 || score == 0 {
-                    remove_list.push(*id);
+        selected
+    }
 
-                    println!(
-                        "[select_tip] Removing tip: solid={}, selected={}, score={}",
-                        tip.metadata.solid, tip.metadata.selected, score
-                    );
+    pub fn num_semi_lazy_tips(&self) -> usize {
+        self.semi_lazy
+            .lock()
+            .expect("poisoned semi-lazy pool")
+            .tips
+            .len()
+    }
 
-                    continue;
-                }
+    /// Moves a tip, that was scored semi-lazy, from the tip pool into the semi-lazy pool.
+    pub(crate) fn add_semi_lazy(&self, id: &InternedHash) {
+        if self.tips.remove(id).is_some() {
+            self.events.emit(Event::TipRemoved { id: *id });
+        }
 
-                //println!("[select_tip] Added a valid tip with id={}, score={}", id, score);
+        let evicted = self
+            .semi_lazy
+            .lock()
+            .expect("poisoned semi-lazy pool")
+            .add(*id);
 
-                valid_tips.push((*id, score));
-                score_sum += score;
-            }
-        }
+        self.events.emit(Event::SemiLazyAdded { id: *id });
 
-        for id in remove_list.iter() {
-            self.tips.remove(id);
+        for evicted in evicted {
+            self.events.emit(Event::SemiLazyRemoved {
+                id: evicted,
+                reason: "limit",
+            });
         }
+    }
 
-        println!(
-            "[select_tip] Removed {} tips from tip pool",
-            remove_list.len()
-        );
-
-        // TODO: randomly select tip
-        let mut rng = rand::thread_rng();
-        let mut random_number = rng.gen_range(1, score_sum);
+    fn remove_semi_lazy(&self, id: &InternedHash, reason: &'static str) {
+        let removed = self
+            .semi_lazy
+            .lock()
+            .expect("poisoned semi-lazy pool")
+            .remove(id);
 
-        println!("[select_tip] Num Elligible Tips = {}", valid_tips.len());
+        if removed {
+            self.events.emit(Event::SemiLazyRemoved { id: *id, reason });
+        }
+    }
 
-        for (id, score) in valid_tips.iter() {
-            random_number -= score;
-            if random_number <= 0 {
-                if let Some(mut tip) = self.vertices.get_mut(id) {
-                    tip.metadata.selected += 1;
+    // Picks the semi-lazy tip to promote; the ones that have become lazy in the meantime are dropped on the way.
+    fn promote(&self) -> Option<InternedHash> {
+        let candidates = self
+            .semi_lazy
+            .lock()
+            .expect("poisoned semi-lazy pool")
+            .candidates();
+
+        for id in candidates {
+            let score = match (self.get_otrsi(&id), self.get_ytrsi(&id)) {
+                (Some(otrsi), Some(ytrsi)) if self.vertices.contains_key(&id) => {
+                    // NOTE: not `get_tip_score`, as the statistics of the scores are about the tip pool
+                    self.compute_tip_score(&id, otrsi, ytrsi).0
                 }
+                _ => Score::Lazy,
+            };
 
-                println!(
-                    "[select_tip] Selected tip with id={} in {:?}",
-                    id,
-                    now.elapsed()
-                );
-                return Some(*id);
+            if score == Score::Lazy {
+                self.remove_semi_lazy(&id, "lazy");
+                continue;
+            }
+
+            if self
+                .semi_lazy
+                .lock()
+                .expect("poisoned semi-lazy pool")
+                .remove(&id)
+            {
+                self.events.emit(Event::Promoted { id });
             }
+
+            return Some(id);
         }
 
-        println!("[select_tip] Found no tip in {:?}", now.elapsed());
         None
     }
 
     #[inline]
-    fn get_tip_score(
+    pub(crate) fn get_tip_score(
         &self,
         id: &InternedHash,
         otrsi: MilestoneIndex,
         ytrsi: MilestoneIndex,
     ) -> Score {
-        // NOTE: unwrap should be safe
-        let vertex = self.vertices.get(&id).unwrap();
+        let (score, reason) = self.compute_tip_score(id, otrsi, ytrsi);
 
-        if self.lsmi.load(Ordering::Relaxed) - ytrsi > YTRSI_DELTA {
-            println!("[get_score ] YTRSI for {} too old", id);
+        self.events.emit(Event::ScoreComputed {
+            id: *id,
+            score,
+            reason,
+        });
 
-            return Score::Lazy;
-        }
-
-        if self.lsmi.load(Ordering::Relaxed) - otrsi > BELOW_MAX_DEPTH {
-            println!("[get_score ] OTRSI for {} too old (below max depth)", id);
+        score
+    }
 
-            return Score::Lazy;
+    fn compute_tip_score(
+        &self,
+        id: &InternedHash,
+        otrsi: MilestoneIndex,
+        ytrsi: MilestoneIndex,
+    ) -> (Score, &'static str) {
+        // NOTE: unwrap should be safe
+        let Parents { ma, pa } = self.vertices.get(id).unwrap().parents.clone();
+        let TsaConfig {
+            ytrsi_delta,
+            otrsi_delta,
+            below_max_depth,
+        } = self.tsa_config();
+
+        // NOTE: an RSI can be ahead of the LSMI, if a milestone confirmed it before it became solid itself
+        if self.lsmi.load(Ordering::Relaxed).saturating_sub(ytrsi) > ytrsi_delta {
+            return (Score::Lazy, "ytrsi too old");
         }
 
-        let Parents { ma, pa } = vertex.parents;
+        if self.lsmi.load(Ordering::Relaxed).saturating_sub(otrsi) > below_max_depth {
+            return (Score::Lazy, "otrsi too old (below max depth)");
+        }
 
         let mut parent_otrsi_check = 2;
 
-        if let Some(ma) = self.vertices.get(&ma) {
+        // NOTE: the parents might have been pruned (or evicted) already, so they are looked up everywhere
+        if let Some(otrsi) = self.get_otrsi(&ma) {
             // NOTE: removed as suggested by muxxer
             // if ma.score.unwrap_or(Score::NonLazy) == Score::Lazy {
             //     return Score::Lazy;
             // }
 
-            if self.lsmi.load(Ordering::Relaxed) - ma.metadata.otrsi.unwrap().0 > OTRSI_DELTA {
+            if self.lsmi.load(Ordering::Relaxed).saturating_sub(otrsi) > otrsi_delta {
                 parent_otrsi_check -= 1;
             }
         }
 
-        if let Some(pa) = self.vertices.get(&pa) {
+        if let Some(otrsi) = self.get_otrsi(&pa) {
             // NOTE: removed as suggested by muxxer
             // if pa.score.unwrap_or(Score::NonLazy) == Score::Lazy {
             //     return Score::Lazy;
             // }
 
-            if self.lsmi.load(Ordering::Relaxed) - pa.metadata.otrsi.unwrap().0 > OTRSI_DELTA {
+            if self.lsmi.load(Ordering::Relaxed).saturating_sub(otrsi) > otrsi_delta {
                 parent_otrsi_check -= 1;
             }
         }
 
         if parent_otrsi_check == 0 {
-            println!("[get_score ] both parents failed 'parent_otrsi_check");
-
-            return Score::Lazy;
+            return (Score::Lazy, "both parents failed the parent otrsi check");
         }
 
         if parent_otrsi_check == 1 {
-            println!(
-                "[get_score ] one of the parents failed 'parent_otrsi_check (makes tip semi-lazy)"
+            return (
+                Score::SemiLazy,
+                "one of the parents failed the parent otrsi check",
             );
-
-            return Score::SemiLazy;
         }
 
-        Score::NonLazy
+        (Score::NonLazy, "all checks passed")
     }
 
     // TODO: remove this method eventually
     // For a given transaction finds all CRTs (confirmed root transactins).
     // NOTE: This method is not used during runtime. It's just to check that the OTRSI and YTRSI values are correctly propagated!
     // The first version of this prototype used it, and it was very very slow!
-    pub fn scan_confirmed_root_transactions(&self, id: &InternedHash) -> Option<(OTRSI, YTRSI)> {
+    pub fn scan_confirmed_root_transactions(&self, id: &InternedHash) -> Option<(Otrsi, Ytrsi)> {
         let mut visited = vec![*id];
         let mut collected = std::collections::HashSet::new();
 
@@ -647,7 +1112,12 @@ impl Tangle {
 
 #[cfg(test)]
 mod tests {
-    use super::*;
+    use super::{tsa, Message, MessageKind, Promotion, Tangle, TsaConfig};
+    use crate::stats::Stats;
+    use crate::utils::*;
+
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
 
     #[test]
     fn one_milestone() {
@@ -722,6 +1192,225 @@ mod tests {
         assert_eq!(Some((1, 2)), tangle.scan_confirmed_root_transactions(&26));
     }
 
+    #[test]
+    fn out_of_order_milestones() {
+        let tangle = make_tangle_out_of_order_milestones();
+
+        for i in 1..=26 {
+            assert!(tangle.is_solid(&i));
+        }
+
+        let confirmed = [
+            (1, 1),
+            (2, 1),
+            (3, 1),
+            (5, 1),
+            (6, 1),
+            (8, 1),
+            (9, 2),
+            (11, 2),
+            (15, 2),
+        ];
+
+        for id in 1..26 {
+            let index = confirmed
+                .iter()
+                .find(|(confirmed, _)| *confirmed == id)
+                .map(|(_, index)| *index);
+
+            assert_eq!(index, tangle.get(&id).unwrap().confirmed, "vertex {}", id);
+        }
+
+        assert_eq!(2, tangle.lmi.load(Ordering::Relaxed));
+        assert_eq!(2, tangle.lsmi.load(Ordering::Relaxed));
+        assert_eq!(0, tangle.missing.len());
+
+        assert_eq!(Some((1, 1)), tangle.scan_confirmed_root_transactions(&23));
+        assert_eq!(Some((1, 2)), tangle.scan_confirmed_root_transactions(&24));
+        assert_eq!(Some((1, 2)), tangle.scan_confirmed_root_transactions(&26));
+    }
+
+    // NOTE: the final state of a tangle must not depend on the order its messages arrived in
+    #[test]
+    fn arrival_order_independence() {
+        let fixtures = [make_tangle_1_milestone(), make_tangle_2_milestones()];
+        let orders = [Order::Reversed]
+            .iter()
+            .copied()
+            .chain((0..50).map(Order::Shuffled))
+            .collect::<Vec<_>>();
+
+        for fixture in &fixtures {
+            let expected = replay(fixture, Order::Forward);
+
+            for order in &orders {
+                let tangle = replay(fixture, *order);
+
+                for vertex in expected.vertices.iter() {
+                    let id = vertex.key();
+                    let metadata = tangle.get(id).unwrap();
+
+                    assert!(metadata.solid, "{:?}: {} not solid", order, id);
+                    assert_eq!(
+                        vertex.metadata.confirmed, metadata.confirmed,
+                        "{:?}: confirmation of {}",
+                        order, id
+                    );
+                    assert_eq!(
+                        expected.get_otrsi(id),
+                        tangle.get_otrsi(id),
+                        "{:?}: OTRSI of {}",
+                        order,
+                        id
+                    );
+                    assert_eq!(
+                        expected.get_ytrsi(id),
+                        tangle.get_ytrsi(id),
+                        "{:?}: YTRSI of {}",
+                        order,
+                        id
+                    );
+                    assert_eq!(
+                        tangle.scan_confirmed_root_transactions(id),
+                        tangle.get_otrsi(id).zip(tangle.get_ytrsi(id)),
+                        "{:?}: root transactions of {}",
+                        order,
+                        id
+                    );
+
+                    // NOTE: the cumulative weight of a confirmed vertex depends on when it got confirmed
+                    if metadata.confirmed.is_none() {
+                        assert_eq!(
+                            tangle.future_cone_weight(id),
+                            metadata.cumulative_weight,
+                            "{:?}: cumulative weight of {}",
+                            order,
+                            id
+                        );
+                    }
+                }
+
+                let mut tips = tangle.tips.iter().map(|tip| *tip).collect::<Vec<_>>();
+                let mut expected_tips = expected.tips.iter().map(|tip| *tip).collect::<Vec<_>>();
+                tips.sort_unstable();
+                expected_tips.sort_unstable();
+
+                assert_eq!(expected_tips, tips, "{:?}: tips", order);
+                assert_eq!(
+                    expected.lsmi.load(Ordering::Relaxed),
+                    tangle.lsmi.load(Ordering::Relaxed)
+                );
+                assert_eq!(
+                    expected.lmi.load(Ordering::Relaxed),
+                    tangle.lmi.load(Ordering::Relaxed)
+                );
+                assert_eq!(0, tangle.missing.len());
+            }
+        }
+    }
+
+    #[test]
+    fn confidence() {
+        let tangle = make_tangle_1_milestone();
+
+        assert_eq!(Some(1.0), tangle.confidence(&12));
+        assert_eq!(Some(0.75), tangle.confidence(&19));
+        assert_eq!(Some(0.5), tangle.confidence(&22));
+        assert_eq!(Some(0.25), tangle.confidence(&26));
+        assert_eq!(None, tangle.confidence(&99));
+
+        let confidences = tangle.confidences();
+
+        assert!(!confidences.contains_key(&12));
+        for (id, confidence) in confidences {
+            assert_eq!(tangle.confidence(&id), Some(confidence), "{}", id);
+        }
+
+        // NOTE: a tip below max depth doesn't count
+        tangle.set_tsa_config(TsaConfig {
+            below_max_depth: 0,
+            ..TsaConfig::default()
+        });
+        tangle.insert_gossip(27, Message::default(), 0, 0);
+
+        assert_eq!(Some(0.5), tangle.confidence(&22));
+    }
+
+    #[test]
+    fn semi_lazy_promotion() {
+        let tangle = make_tangle_1_milestone()
+            .with_tsa_config(TsaConfig {
+                otrsi_delta: 0,
+                ..TsaConfig::default()
+            })
+            .with_semi_lazy_pool(10, Promotion::Youngest);
+        let stats = Arc::new(Stats::default());
+        tangle.events.subscribe(Arc::clone(&stats) as _);
+
+        // NOTE: the OTRSI of solid entry point 0 is too old for C2=0, which makes 27 semi-lazy
+        tangle.insert_gossip(27, Message::default(), 0, 26);
+
+        let (tip1, tip2) = tangle.select_two_tips().unwrap();
+
+        assert!((23..=25).contains(&tip1));
+        assert_eq!(27, tip2);
+        assert_eq!(0, tangle.num_semi_lazy_tips());
+        assert!(!tangle.tips.contains(&27));
+
+        tangle.insert_own(28, Message::default(), tip1, tip2);
+        tangle.insert_gossip(29, Message::new((), MessageKind::Milestone(2)), 28, 28);
+
+        assert_eq!(Some(true), tangle.confirmed(&27));
+
+        let summary = stats.summary(&tangle, 0);
+
+        assert_eq!(1, summary.semi_lazy_tips);
+        assert_eq!(1, summary.promoted);
+        assert_eq!(1, summary.semi_lazy_confirmed);
+        assert_eq!(0, summary.semi_lazy_dropped);
+    }
+
+    #[test]
+    fn semi_lazy_weighted() {
+        let tangle = make_tangle_1_milestone().with_tsa_config(TsaConfig {
+            otrsi_delta: 0,
+            ..TsaConfig::default()
+        });
+
+        tangle.insert_gossip(27, Message::default(), 0, 26);
+
+        // NOTE: by default, the semi-lazy 27 stays in the tip pool with half the weight of the non-lazy tips
+        let selected = (0..50)
+            .filter_map(|_| tangle.select_tip())
+            .collect::<Vec<_>>();
+
+        assert!(selected.contains(&27));
+        assert_eq!(0, tangle.num_semi_lazy_tips());
+    }
+
+    #[test]
+    fn unsolid_milestones_wait() {
+        let tangle = Tangle::new();
+
+        tangle.add_solid_entrypoint(0, 0);
+
+        tangle.insert_gossip(1, Message::default(), 0, 0);
+        tangle.insert_gossip(3, Message::new((), MessageKind::Milestone(1)), 1, 2); // MS 1
+        tangle.insert_gossip(4, Message::new((), MessageKind::Milestone(2)), 1, 1); // MS 2
+
+        // NOTE: milestone 2 is solid, but has to wait for milestone 1
+        assert!(tangle.is_solid(&4));
+        assert_eq!(0, tangle.lsmi.load(Ordering::Relaxed));
+        assert_eq!(Some(false), tangle.confirmed(&1));
+
+        tangle.insert_gossip(2, Message::default(), 0, 0);
+
+        assert_eq!(2, tangle.lsmi.load(Ordering::Relaxed));
+        assert_eq!(Some(1), tangle.get(&1).unwrap().confirmed);
+        assert_eq!(Some(1), tangle.get(&2).unwrap().confirmed);
+        assert_eq!(Some(2), tangle.get(&4).unwrap().confirmed);
+    }
+
     #[test]
     fn four_tips() {
         let tangle = make_tangle_1_milestone();
@@ -730,4 +1419,38 @@ mod tests {
         assert_eq!(0, tangle.missing.len());
         assert_eq!(1, tangle.seps.len());
     }
+
+    #[test]
+    fn evicted_vertices() {
+        let tangle = make_tangle_1_milestone();
+
+        for id in 1..=12 {
+            assert!(tangle.evict(&id).unwrap());
+        }
+
+        assert!(!tangle.evict(&12).unwrap());
+        assert_eq!(12, tangle.storage().len());
+        assert!(tangle.is_solid(&8));
+        assert_eq!(Some(1), tangle.get_otrsi(&12));
+        assert!(!tangle.vertices.get(&15).unwrap().children.is_empty());
+        assert!(tangle.vertices.get(&13).is_some());
+
+        // NOTE: attaching to an evicted vertex doesn't make it a missing parent
+        tangle.insert_gossip(27, Message::default(), 12, 11);
+
+        assert_eq!(0, tangle.missing.len());
+        assert!(tangle.is_solid(&27));
+        assert_eq!(Some(1), tangle.get_otrsi(&27));
+    }
+
+    #[test]
+    fn select_two_tips() {
+        let tangle =
+            make_tangle_1_milestone().with_tip_selector(tsa::by_name("heuristic").unwrap());
+
+        let (tip1, tip2) = tangle.select_two_tips().unwrap();
+
+        assert!(tip1 > 20 && tip2 > 20);
+        assert_eq!(None, tsa::by_name("unknown").map(|tsa| tsa.name()));
+    }
 }
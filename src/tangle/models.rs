@@ -1,4 +1,3 @@
-use std::cmp::Ordering;
 use std::sync::atomic::AtomicU64;
 
 use dashmap::DashSet as HashSet;
@@ -6,27 +5,16 @@ use dashmap::DashSet as HashSet;
 pub type InternedHash = u64;
 pub type MilestoneIndex = u64;
 pub type AtomicMilestoneIndex = AtomicU64;
-pub type OTRSI = MilestoneIndex;
-pub type YTRSI = MilestoneIndex;
+pub type Otrsi = MilestoneIndex;
+pub type Ytrsi = MilestoneIndex;
 pub type Confirmation = Option<MilestoneIndex>;
 pub type Children = HashSet<InternedHash>;
 pub type Payload = (); // this would be a: `bee-transaction::bundled::BundledTransaction`
 
-#[derive(Clone, Copy, Debug, Default, Ord, Eq)]
+// NOTE: ties on the index are broken by the id, so that `min`/`max` pick a deterministic parent
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct IndexId(pub MilestoneIndex, pub InternedHash);
 
-impl PartialOrd for IndexId {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.0.partial_cmp(&other.0)
-    }
-}
-
-impl PartialEq for IndexId {
-    fn eq(&self, other: &Self) -> bool {
-        self.0 == other.0
-    }
-}
-
 #[derive(Clone, Default)]
 pub struct Parents {
     pub ma: InternedHash,
@@ -45,8 +33,9 @@ impl Message {
     }
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Default)]
 pub enum MessageKind {
+    #[default]
     Data,
     Value,
     Checkpoint,
@@ -55,17 +44,7 @@ pub enum MessageKind {
 
 impl MessageKind {
     pub fn is_milestone(&self) -> bool {
-        if let Self::Milestone(_) = *self {
-            true
-        } else {
-            false
-        }
-    }
-}
-
-impl Default for MessageKind {
-    fn default() -> Self {
-        Self::Data
+        matches!(*self, Self::Milestone(_))
     }
 }
 
@@ -76,6 +55,19 @@ pub struct Metadata {
     pub otrsi: Option<IndexId>, // can only be missing if ma and pa were missing; same for ytrsi
     pub ytrsi: Option<IndexId>,
     pub selected: u8, //number of times we selected it in the TSA
+    pub weight: u64,  // own weight; every message weighs 1 for now
+    // own weight plus the weights of the future cone; it stops growing once the vertex is confirmed
+    pub cumulative_weight: u64,
+}
+
+impl Metadata {
+    pub fn new(weight: u64) -> Self {
+        Self {
+            weight,
+            cumulative_weight: weight,
+            ..Self::default()
+        }
+    }
 }
 
 #[derive(Default)]
@@ -93,3 +85,13 @@ pub enum Score {
     SemiLazy = 1,
     NonLazy = 2,
 }
+
+impl Score {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Lazy => "lazy",
+            Self::SemiLazy => "semi_lazy",
+            Self::NonLazy => "non_lazy",
+        }
+    }
+}
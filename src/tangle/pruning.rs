@@ -0,0 +1,121 @@
+use super::models::*;
+use super::Tangle;
+
+use crate::events::Event;
+
+use std::collections::HashSet as StdHashSet;
+use std::io;
+
+impl Tangle {
+    /// Evicts all vertices, that were confirmed by a milestone below `index`, into the storage. The pruned vertices,
+    /// that are still referenced by a remaining vertex, become solid entry points with their confirming index, and
+    /// solid entry points, that aren't referenced anymore, are dropped. Returns the number of pruned vertices.
+    pub fn prune(&self, index: MilestoneIndex) -> io::Result<usize> {
+        let mut pruned = self
+            .vertices
+            .iter()
+            .filter(
+                |vertex| matches!(vertex.metadata.confirmed, Some(confirmed) if confirmed < index),
+            )
+            .map(|vertex| *vertex.key())
+            .collect::<Vec<_>>();
+
+        if pruned.is_empty() {
+            return Ok(0);
+        }
+
+        // NOTE: sorted, so that seps are promoted in a reproducible order
+        pruned.sort_unstable();
+
+        let pruned_set = pruned.iter().copied().collect::<StdHashSet<_>>();
+        let mut boundary = Vec::new();
+
+        for id in &pruned {
+            if let Some(vertex) = self.vertices.get(id) {
+                if vertex
+                    .children
+                    .iter()
+                    .any(|child| !pruned_set.contains(&*child))
+                {
+                    // NOTE: a confirmed vertex is its own root, so its confirming index is its OTRSI and YTRSI
+                    boundary.push((*id, vertex.metadata.confirmed.unwrap()));
+                }
+            }
+        }
+
+        // NOTE: the storage keeps answering for the pruned vertices, so that a late child of one isn't left with a
+        // missing parent
+        for id in &pruned {
+            self.evict(id)?;
+        }
+
+        for (id, confirmed) in boundary {
+            self.add_solid_entrypoint(id, confirmed);
+        }
+
+        let referenced = self
+            .vertices
+            .iter()
+            .flat_map(|vertex| vec![vertex.parents.ma, vertex.parents.pa])
+            .collect::<StdHashSet<_>>();
+
+        self.seps
+            .retain(|id, sep_index| *sep_index >= index || referenced.contains(id));
+
+        self.events.emit(Event::Pruned {
+            index,
+            vertices: pruned.len() as u64,
+        });
+
+        Ok(pruned.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::make_tangle_1_milestone;
+
+    #[test]
+    fn prune_one_milestone() {
+        let tangle = make_tangle_1_milestone();
+
+        assert_eq!(0, tangle.prune(1).unwrap());
+        assert_eq!(9, tangle.prune(2).unwrap());
+
+        assert_eq!(26 - 9, tangle.vertices.len());
+        assert_eq!(9, tangle.storage().len());
+        assert_eq!(None, tangle.milestone(1));
+
+        let mut seps = tangle.seps.iter().map(|sep| *sep.key()).collect::<Vec<_>>();
+        seps.sort_unstable();
+
+        assert_eq!(vec![1, 2, 5, 8, 9, 11, 12], seps);
+        assert!(tangle.seps.iter().all(|sep| *sep.value() == 1));
+        assert_eq!(4, tangle.num_tips());
+
+        for vertex in tangle.vertices.iter() {
+            assert!(tangle.is_solid(vertex.key()));
+            assert!(vertex
+                .children
+                .iter()
+                .all(|child| tangle.vertices.contains_key(&*child)));
+        }
+
+        // NOTE: new vertices can still attach to the boundary
+        tangle.insert_gossip(27, Message::default(), 12, 26);
+
+        assert!(tangle.is_solid(&27));
+        assert_eq!(Some(1), tangle.get_otrsi(&27));
+        assert_eq!(0, tangle.missing.len());
+        assert!(tangle.select_tip().is_some());
+
+        // NOTE: a late child of a pruned vertex, that isn't a solid entry point, finds it in the storage
+        assert!(!tangle.seps.contains_key(&3));
+
+        tangle.insert_gossip(28, Message::default(), 3, 27);
+
+        assert!(tangle.is_solid(&28));
+        assert_eq!(0, tangle.missing.len());
+    }
+}
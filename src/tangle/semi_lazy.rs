@@ -0,0 +1,139 @@
+use super::models::*;
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+const SEMI_LAZY_LIMIT: usize = 10;
+
+/// How semi-lazy tips are selected: either like the other tips, or kept apart, from where an own message might
+/// promote one of them as its second parent.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Promotion {
+    /// Semi-lazy tips stay in the tip pool, and are picked with the weight of their score, as in the Protocol RFC 0008.
+    Weighted,
+    /// Semi-lazy tips are only tracked, but never selected.
+    Never,
+    /// The most recent semi-lazy tip, as it is the most likely to still be promotable.
+    Youngest,
+    /// The semi-lazy tip, that has waited the longest, before it becomes lazy.
+    Oldest,
+}
+
+impl FromStr for Promotion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "weighted" => Ok(Self::Weighted),
+            "never" => Ok(Self::Never),
+            "youngest" => Ok(Self::Youngest),
+            "oldest" => Ok(Self::Oldest),
+            _ => Err(format!("unknown promotion policy: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Promotion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Weighted => write!(f, "weighted"),
+            Self::Never => write!(f, "never"),
+            Self::Youngest => write!(f, "youngest"),
+            Self::Oldest => write!(f, "oldest"),
+        }
+    }
+}
+
+/// The tips, that were scored semi-lazy, in the order they were added; kept apart from the other tips, so that the
+/// TSA only ever picks them on purpose.
+pub(super) struct SemiLazyPool {
+    // the most semi-lazy tips kept; at least 1
+    pub limit: usize,
+    pub promotion: Promotion,
+    pub tips: VecDeque<InternedHash>,
+}
+
+impl SemiLazyPool {
+    pub fn new(limit: usize, promotion: Promotion) -> Self {
+        Self {
+            limit,
+            promotion,
+            tips: VecDeque::new(),
+        }
+    }
+
+    /// Adds a tip, and returns the tips, that had to make room for it (which might be the tip itself).
+    pub fn add(&mut self, id: InternedHash) -> Vec<InternedHash> {
+        if self.tips.contains(&id) {
+            return Vec::new();
+        }
+
+        self.tips.push_back(id);
+
+        let mut evicted = Vec::new();
+        while self.tips.len() > self.limit {
+            evicted.extend(self.tips.pop_front());
+        }
+
+        evicted
+    }
+
+    pub fn remove(&mut self, id: &InternedHash) -> bool {
+        match self.tips.iter().position(|tip| tip == id) {
+            Some(position) => self.tips.remove(position).is_some(),
+            None => false,
+        }
+    }
+
+    /// The tips in the order, in which the promotion policy considers them.
+    pub fn candidates(&self) -> Vec<InternedHash> {
+        match self.promotion {
+            Promotion::Weighted | Promotion::Never => Vec::new(),
+            Promotion::Youngest => self.tips.iter().rev().copied().collect(),
+            Promotion::Oldest => self.tips.iter().copied().collect(),
+        }
+    }
+}
+
+impl Default for SemiLazyPool {
+    fn default() -> Self {
+        Self::new(SEMI_LAZY_LIMIT, Promotion::Weighted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limit_and_order() {
+        let mut pool = SemiLazyPool::new(2, Promotion::Youngest);
+
+        assert!(pool.add(1).is_empty());
+        assert!(pool.add(2).is_empty());
+        assert!(pool.add(2).is_empty());
+        assert_eq!(vec![1], pool.add(3));
+        assert_eq!(vec![3, 2], pool.candidates());
+
+        pool.promotion = Promotion::Oldest;
+        assert_eq!(vec![2, 3], pool.candidates());
+
+        assert!(pool.remove(&2));
+        assert!(!pool.remove(&2));
+
+        pool.promotion = Promotion::Never;
+        assert!(pool.candidates().is_empty());
+
+        pool.promotion = Promotion::Weighted;
+        assert!(pool.candidates().is_empty());
+
+        // NOTE: with room for a single tip, the older one makes room for the new one
+        let mut pool = SemiLazyPool::new(1, Promotion::Oldest);
+        assert!(pool.add(4).is_empty());
+        assert_eq!(vec![4], pool.add(5));
+
+        assert_eq!(Ok(Promotion::Oldest), "oldest".parse());
+        assert_eq!(Ok(Promotion::Weighted), "weighted".parse());
+    }
+}
@@ -0,0 +1,375 @@
+use super::models::*;
+use super::Tangle;
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::sync::atomic::Ordering;
+
+use dashmap::DashSet as HashSet;
+
+// NOTE: bump this whenever the format changes, and keep reading the older versions if possible
+const VERSION: u32 = 3;
+const HEADER: &str = "wurts-up tangle v";
+
+// The file format is line based and meant to be readable (and editable) by hand:
+//
+//   wurts-up tangle v3
+//   lmi 2
+//   lsmi 2
+//   sep 0 0
+//   vertex 15 11 9 milestone:2 solid=1 confirmed=2 otrsi=2:15 ytrsi=2:15 selected=0
+//     weight=1 cumulative_weight=9
+//   tip 26
+//   semi_lazy 25
+//   missing 30 31,32
+//
+// (where a vertex is written on a single line). The children of a vertex aren't stored, as they are exactly the
+// vertices that have it as a parent. The semi-lazy tips are listed from the oldest to the youngest. Version 1 didn't
+// store the weights; they are recomputed from the future cones. Version 2 didn't store the semi-lazy tips.
+impl Tangle {
+    /// Writes the state of the tangle (but not its TSA and subscribers) in the tangle file format.
+    pub fn write_to<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(out, "{}{}", HEADER, VERSION)?;
+        writeln!(out, "lmi {}", self.lmi.load(Ordering::Relaxed))?;
+        writeln!(out, "lsmi {}", self.lsmi.load(Ordering::Relaxed))?;
+
+        // NOTE: sorted, so that the same tangle is always written the same way
+        let mut seps = self
+            .seps
+            .iter()
+            .map(|entry| (*entry.key(), *entry.value()))
+            .collect::<Vec<_>>();
+        seps.sort_unstable();
+
+        for (id, index) in seps {
+            writeln!(out, "sep {} {}", id, index)?;
+        }
+
+        let mut ids = self
+            .vertices
+            .iter()
+            .map(|entry| *entry.key())
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+
+        for id in ids {
+            if let Some(vertex) = self.vertices.get(&id) {
+                let Metadata {
+                    solid,
+                    confirmed,
+                    otrsi,
+                    ytrsi,
+                    selected,
+                    weight,
+                    cumulative_weight,
+                } = vertex.metadata;
+
+                writeln!(
+                    out,
+                    "vertex {} {} {} {} solid={} confirmed={} otrsi={} ytrsi={} selected={} weight={} \
+                     cumulative_weight={}",
+                    id,
+                    vertex.parents.ma,
+                    vertex.parents.pa,
+                    write_kind(&vertex.message.kind),
+                    solid as u8,
+                    confirmed.map_or("-".into(), |index| index.to_string()),
+                    write_index_id(otrsi),
+                    write_index_id(ytrsi),
+                    selected,
+                    weight,
+                    cumulative_weight
+                )?;
+            }
+        }
+
+        let mut tips = self.tips.iter().map(|id| *id).collect::<Vec<_>>();
+        tips.sort_unstable();
+
+        for id in tips {
+            writeln!(out, "tip {}", id)?;
+        }
+
+        let semi_lazy = self
+            .semi_lazy
+            .lock()
+            .expect("poisoned semi-lazy pool")
+            .tips
+            .clone();
+
+        for id in semi_lazy {
+            writeln!(out, "semi_lazy {}", id)?;
+        }
+
+        let mut missing = self
+            .missing
+            .iter()
+            .map(|entry| {
+                let mut children = entry.value().iter().map(|id| *id).collect::<Vec<_>>();
+                children.sort_unstable();
+                (*entry.key(), children)
+            })
+            .collect::<Vec<_>>();
+        missing.sort_unstable();
+
+        for (id, children) in missing {
+            let children = children
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+
+            writeln!(out, "missing {} {}", id, children)?;
+        }
+
+        out.flush()
+    }
+
+    /// Reads a tangle from the tangle file format; it comes with the default TSA and no subscribers.
+    pub fn read_from<R: BufRead>(input: R) -> Result<Self, String> {
+        let tangle = Tangle::new();
+        let mut lines = input.lines().enumerate();
+
+        let header = match lines.next() {
+            Some((_, line)) => line.map_err(|e| e.to_string())?,
+            None => return Err("empty tangle file".into()),
+        };
+        let version = header
+            .strip_prefix(HEADER)
+            .and_then(|version| version.trim().parse::<u32>().ok())
+            .ok_or_else(|| format!("not a tangle file: {}", header))?;
+
+        if version == 0 || version > VERSION {
+            return Err(format!("unsupported tangle file version: {}", version));
+        }
+
+        for (number, line) in lines {
+            let line = line.map_err(|e| e.to_string())?;
+            let line = line.trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            read_line(&tangle, line).map_err(|e| format!("line {}: {}", number + 1, e))?;
+        }
+
+        // NOTE: restore the children from the parents
+        let edges = tangle
+            .vertices
+            .iter()
+            .map(|entry| (*entry.key(), entry.value().parents.clone()))
+            .collect::<Vec<_>>();
+
+        for (id, Parents { ma, pa }) in edges {
+            for parent in &[ma, pa] {
+                if let Some(parent) = tangle.vertices.get_mut(parent) {
+                    parent.children.insert(id);
+                }
+            }
+        }
+
+        if version < 2 {
+            let ids = tangle
+                .vertices
+                .iter()
+                .map(|entry| *entry.key())
+                .collect::<Vec<_>>();
+
+            for id in &ids {
+                if let Some(mut vertex) = tangle.vertices.get_mut(id) {
+                    vertex.metadata.weight = 1;
+                }
+            }
+
+            // NOTE: the arrival order is lost, so a confirmed vertex only counts the approvers confirmed by the same
+            // milestone, and not the ones that arrived before its confirmation, but were confirmed later
+            for id in &ids {
+                let cumulative_weight = tangle.future_cone_weight(id);
+
+                if let Some(mut vertex) = tangle.vertices.get_mut(id) {
+                    vertex.metadata.cumulative_weight = cumulative_weight;
+                }
+            }
+        }
+
+        // NOTE: ... and the milestones by index
+        for vertex in tangle.vertices.iter() {
+            if let MessageKind::Milestone(index) = vertex.message.kind {
+                tangle.milestones.insert(index, *vertex.key());
+            }
+        }
+
+        Ok(tangle)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        File::create(path)
+            .and_then(|file| self.write_to(BufWriter::new(file)))
+            .map_err(|e| format!("cannot write {}: {}", path, e))
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+
+        Self::read_from(BufReader::new(file)).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+fn read_line(tangle: &Tangle, line: &str) -> Result<(), String> {
+    let mut words = line.split_whitespace();
+
+    match words.next().unwrap_or("") {
+        "lmi" => tangle.lmi.store(parse(words.next())?, Ordering::Relaxed),
+        "lsmi" => tangle.lsmi.store(parse(words.next())?, Ordering::Relaxed),
+        "sep" => tangle.add_solid_entrypoint(parse(words.next())?, parse(words.next())?),
+        "vertex" => {
+            let id = parse(words.next())?;
+            let ma = parse(words.next())?;
+            let pa = parse(words.next())?;
+            let kind = read_kind(words.next().unwrap_or(""))?;
+            let mut metadata = Metadata::default();
+
+            for word in words {
+                let (key, value) = word
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected `key=value`: {}", word))?;
+
+                match key {
+                    "solid" => metadata.solid = parse::<u8>(Some(value))? != 0,
+                    "confirmed" if value == "-" => metadata.confirmed = None,
+                    "confirmed" => metadata.confirmed = Some(parse(Some(value))?),
+                    "otrsi" => metadata.otrsi = read_index_id(value)?,
+                    "ytrsi" => metadata.ytrsi = read_index_id(value)?,
+                    "selected" => metadata.selected = parse(Some(value))?,
+                    "weight" => metadata.weight = parse(Some(value))?,
+                    "cumulative_weight" => metadata.cumulative_weight = parse(Some(value))?,
+                    _ => return Err(format!("unknown vertex field: {}", key)),
+                }
+            }
+
+            tangle.vertices.insert(
+                id,
+                Vertex {
+                    parents: Parents { ma, pa },
+                    children: HashSet::new(),
+                    message: Message::new((), kind),
+                    metadata,
+                },
+            );
+        }
+        "tip" => {
+            tangle.tips.insert(parse(words.next())?);
+        }
+        "semi_lazy" => {
+            let id = parse(words.next())?;
+
+            tangle
+                .semi_lazy
+                .lock()
+                .expect("poisoned semi-lazy pool")
+                .tips
+                .push_back(id);
+        }
+        "missing" => {
+            let id = parse(words.next())?;
+            let children = tangle.missing.entry(id).or_default();
+
+            for child in words.next().unwrap_or("").split(',') {
+                children.insert(parse(Some(child))?);
+            }
+        }
+        other => return Err(format!("unknown entry: {}", other)),
+    }
+
+    Ok(())
+}
+
+pub(super) fn parse<T: std::str::FromStr>(word: Option<&str>) -> Result<T, String> {
+    let word = word.ok_or("missing value")?;
+
+    word.parse().map_err(|_| format!("invalid value: {}", word))
+}
+
+fn write_kind(kind: &MessageKind) -> String {
+    match kind {
+        MessageKind::Data => "data".into(),
+        MessageKind::Value => "value".into(),
+        MessageKind::Checkpoint => "checkpoint".into(),
+        MessageKind::Milestone(index) => format!("milestone:{}", index),
+    }
+}
+
+pub(super) fn read_kind(word: &str) -> Result<MessageKind, String> {
+    match word {
+        "data" => Ok(MessageKind::Data),
+        "value" => Ok(MessageKind::Value),
+        "checkpoint" => Ok(MessageKind::Checkpoint),
+        _ => match word.strip_prefix("milestone:") {
+            Some(index) => Ok(MessageKind::Milestone(parse(Some(index))?)),
+            None => Err(format!("unknown message kind: {}", word)),
+        },
+    }
+}
+
+fn write_index_id(index_id: Option<IndexId>) -> String {
+    match index_id {
+        Some(IndexId(index, id)) => format!("{}:{}", index, id),
+        None => "-".into(),
+    }
+}
+
+fn read_index_id(word: &str) -> Result<Option<IndexId>, String> {
+    if word == "-" {
+        return Ok(None);
+    }
+
+    let (index, id) = word
+        .split_once(':')
+        .ok_or_else(|| format!("expected `index:id`: {}", word))?;
+
+    Ok(Some(IndexId(parse(Some(index))?, parse(Some(id))?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::make_tangle_2_milestones;
+
+    #[test]
+    fn round_trip() {
+        let tangle = make_tangle_2_milestones();
+        tangle.insert_gossip(30, Message::default(), 26, 29);
+        tangle.add_semi_lazy(&25);
+
+        let mut written = Vec::new();
+        tangle.write_to(&mut written).unwrap();
+
+        let loaded = Tangle::read_from(&written[..]).unwrap();
+        let mut rewritten = Vec::new();
+        loaded.write_to(&mut rewritten).unwrap();
+
+        assert_eq!(
+            String::from_utf8(written).unwrap(),
+            String::from_utf8(rewritten).unwrap()
+        );
+        assert_eq!(tangle.to_dot(), loaded.to_dot());
+        assert_eq!(2, loaded.lsmi.load(Ordering::Relaxed));
+        assert_eq!(3, loaded.vertices.get(&11).unwrap().children.len());
+        assert!(loaded.missing.get(&29).unwrap().contains(&30));
+        assert_eq!(1, loaded.num_semi_lazy_tips());
+
+        assert_eq!(tangle.cumulative_weight(&19), loaded.cumulative_weight(&19));
+
+        assert!(Tangle::read_from(&b"wurts-up tangle v99\n"[..]).is_err());
+        assert!(Tangle::read_from(&b"wurts-up tangle v3\nfoo 1\n"[..]).is_err());
+
+        let old = Tangle::read_from(
+            &b"wurts-up tangle v1\nsep 0 0\nvertex 1 0 0 data\nvertex 2 1 0 data\nvertex 3 1 2 data\n"[..],
+        )
+        .unwrap();
+
+        assert_eq!(3, old.cumulative_weight(&1));
+        assert_eq!(1, old.cumulative_weight(&3));
+    }
+}
@@ -0,0 +1,235 @@
+use super::models::*;
+use super::serialize::parse;
+use super::Tangle;
+
+use std::collections::HashSet as StdHashSet;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::sync::atomic::Ordering;
+
+const VERSION: u32 = 2;
+const HEADER: &str = "wurts-up snapshot v";
+
+/// A local snapshot: everything a node needs to (re)start at a milestone index without the confirmed history below
+/// it. There is no ledger state, as messages don't carry a payload in this simulation.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Snapshot {
+    // the index the snapshot was taken at, which becomes the LSMI of a tangle started from it
+    pub index: MilestoneIndex,
+    pub lmi: MilestoneIndex,
+    pub seps: Vec<(InternedHash, MilestoneIndex)>,
+    // the solid entry points, that are milestones, by index
+    pub milestones: Vec<(MilestoneIndex, InternedHash)>,
+}
+
+impl Tangle {
+    /// Takes a local snapshot at `index`, which must already be solid. The solid entry points are the vertices
+    /// confirmed at or below `index`, that are approved by a vertex outside of that history (or not at all), and
+    /// the existing solid entry points, that are still approved by such a vertex.
+    pub fn local_snapshot(&self, index: MilestoneIndex) -> Result<Snapshot, String> {
+        let lsmi = self.lsmi.load(Ordering::Relaxed);
+
+        if index > lsmi {
+            return Err(format!(
+                "cannot snapshot at {}, as the LSMI is only {}",
+                index, lsmi
+            ));
+        }
+
+        let history = self
+            .vertices
+            .iter()
+            .filter(
+                |vertex| matches!(vertex.metadata.confirmed, Some(confirmed) if confirmed <= index),
+            )
+            .map(|vertex| *vertex.key())
+            .collect::<StdHashSet<_>>();
+
+        let mut seps = Vec::new();
+
+        for vertex in self.vertices.iter() {
+            let id = *vertex.key();
+
+            if history.contains(&id) {
+                if vertex.children.is_empty()
+                    || vertex
+                        .children
+                        .iter()
+                        .any(|child| !history.contains(&*child))
+                {
+                    seps.push((id, vertex.metadata.confirmed.unwrap()));
+                }
+            } else {
+                for parent in &[vertex.parents.ma, vertex.parents.pa] {
+                    if let Some(sep_index) = self.seps.get(parent) {
+                        seps.push((*parent, *sep_index));
+                    }
+                }
+            }
+        }
+
+        // NOTE: sorted, so that the same tangle always results in the same snapshot
+        seps.sort_unstable();
+        seps.dedup();
+
+        let sep_ids = seps.iter().map(|(id, _)| *id).collect::<StdHashSet<_>>();
+        let mut milestones = self
+            .milestones
+            .iter()
+            .filter(|milestone| *milestone.key() <= index && sep_ids.contains(milestone.value()))
+            .map(|milestone| (*milestone.key(), *milestone.value()))
+            .collect::<Vec<_>>();
+        milestones.sort_unstable();
+
+        Ok(Snapshot {
+            index,
+            lmi: self.lmi.load(Ordering::Relaxed),
+            seps,
+            milestones,
+        })
+    }
+
+    /// Starts a tangle from a local snapshot, the way a node does after a restart.
+    pub fn from_snapshot(snapshot: &Snapshot) -> Self {
+        let tangle = Tangle::new();
+
+        for (id, index) in &snapshot.seps {
+            tangle.add_solid_entrypoint(*id, *index);
+        }
+
+        for (index, id) in &snapshot.milestones {
+            tangle.milestones.insert(*index, *id);
+        }
+
+        tangle.lsmi.store(snapshot.index, Ordering::Relaxed);
+        tangle
+            .lmi
+            .store(snapshot.lmi.max(snapshot.index), Ordering::Relaxed);
+
+        tangle
+    }
+}
+
+impl Snapshot {
+    pub fn write_to<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(out, "{}{}", HEADER, VERSION)?;
+        writeln!(out, "index {}", self.index)?;
+        writeln!(out, "lmi {}", self.lmi)?;
+
+        for (id, index) in &self.seps {
+            writeln!(out, "sep {} {}", id, index)?;
+        }
+
+        for (index, id) in &self.milestones {
+            writeln!(out, "milestone {} {}", index, id)?;
+        }
+
+        out.flush()
+    }
+
+    pub fn read_from<R: BufRead>(input: R) -> Result<Self, String> {
+        let mut snapshot = Snapshot::default();
+        let mut lines = input.lines().enumerate();
+
+        let header = match lines.next() {
+            Some((_, line)) => line.map_err(|e| e.to_string())?,
+            None => return Err("empty snapshot file".into()),
+        };
+        let version = header
+            .strip_prefix(HEADER)
+            .and_then(|version| version.trim().parse::<u32>().ok())
+            .ok_or_else(|| format!("not a snapshot file: {}", header))?;
+
+        // NOTE: version 1 had no milestones
+        if version == 0 || version > VERSION {
+            return Err(format!("unsupported snapshot file version: {}", version));
+        }
+
+        for (number, line) in lines {
+            let line = line.map_err(|e| e.to_string())?;
+            let mut words = line.split_whitespace();
+
+            let result = match words.next() {
+                None => Ok(()),
+                Some("index") => parse(words.next()).map(|index| snapshot.index = index),
+                Some("lmi") => parse(words.next()).map(|lmi| snapshot.lmi = lmi),
+                Some("sep") => parse(words.next()).and_then(|id| {
+                    parse(words.next()).map(|index| snapshot.seps.push((id, index)))
+                }),
+                Some("milestone") => parse(words.next()).and_then(|index| {
+                    parse(words.next()).map(|id| snapshot.milestones.push((index, id)))
+                }),
+                Some(other) => Err(format!("unknown entry: {}", other)),
+            };
+
+            result.map_err(|e| format!("line {}: {}", number + 1, e))?;
+        }
+
+        Ok(snapshot)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        File::create(path)
+            .and_then(|file| self.write_to(BufWriter::new(file)))
+            .map_err(|e| format!("cannot write {}: {}", path, e))
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+
+        Self::read_from(BufReader::new(file)).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::make_tangle_2_milestones;
+
+    #[test]
+    fn restart_from_snapshot() {
+        let tangle = make_tangle_2_milestones();
+
+        assert!(tangle.local_snapshot(3).is_err());
+
+        let snapshot = tangle.local_snapshot(2).unwrap();
+
+        assert_eq!(
+            vec![(1, 1), (2, 1), (5, 1), (8, 1), (9, 2), (11, 2), (15, 2)],
+            snapshot.seps
+        );
+        assert_eq!(vec![(1, 8), (2, 15)], snapshot.milestones);
+
+        let mut written = Vec::new();
+        snapshot.write_to(&mut written).unwrap();
+        let snapshot = Snapshot::read_from(&written[..]).unwrap();
+
+        let restarted = Tangle::from_snapshot(&snapshot);
+
+        assert_eq!(2, restarted.lsmi.load(Ordering::Relaxed));
+        assert_eq!(Some(&15), restarted.milestones.get(&2).as_deref());
+
+        // NOTE: what wasn't confirmed yet is gossiped again after the restart
+        let unconfirmed = (1..=26)
+            .filter(|id| !tangle.confirmed(id).unwrap())
+            .collect::<Vec<_>>();
+
+        for id in &unconfirmed {
+            let vertex = tangle.vertices.get(id).unwrap();
+            restarted.insert_gossip(
+                *id,
+                Message::default(),
+                vertex.parents.ma,
+                vertex.parents.pa,
+            );
+        }
+
+        assert_eq!(0, restarted.missing.len());
+
+        for id in &unconfirmed {
+            assert!(restarted.is_solid(id));
+            assert_eq!(tangle.get_otrsi(id), restarted.get_otrsi(id));
+            assert_eq!(tangle.get_ytrsi(id), restarted.get_ytrsi(id));
+        }
+    }
+}
@@ -0,0 +1,391 @@
+use super::models::*;
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use dashmap::DashMap as HashMap;
+
+/// What the tangle still needs to know about a vertex, that isn't held in memory anymore.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Record {
+    pub solid: bool,
+    pub confirmed: Confirmation,
+    pub otrsi: Option<MilestoneIndex>,
+    pub ytrsi: Option<MilestoneIndex>,
+}
+
+impl From<&Metadata> for Record {
+    fn from(metadata: &Metadata) -> Self {
+        Self {
+            solid: metadata.solid,
+            confirmed: metadata.confirmed,
+            otrsi: metadata.otrsi.map(|index_id| index_id.0),
+            ytrsi: metadata.ytrsi.map(|index_id| index_id.0),
+        }
+    }
+}
+
+/// The backing store of a tangle, that is consulted for every vertex that isn't in memory.
+pub trait Storage: Send + Sync {
+    fn get(&self, id: &InternedHash) -> io::Result<Option<Record>>;
+
+    fn insert(&self, id: InternedHash, record: Record) -> io::Result<()>;
+
+    fn contains(&self, id: &InternedHash) -> io::Result<bool> {
+        Ok(self.get(id)?.is_some())
+    }
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Keeps the records in memory, so they are lost with the process.
+#[derive(Default)]
+pub struct MemoryStorage {
+    records: HashMap<InternedHash, Record>,
+}
+
+impl Storage for MemoryStorage {
+    fn get(&self, id: &InternedHash) -> io::Result<Option<Record>> {
+        Ok(self.records.get(id).map(|record| *record))
+    }
+
+    fn insert(&self, id: InternedHash, record: Record) -> io::Result<()> {
+        self.records.insert(id, record);
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.records.len()
+    }
+}
+
+const MAGIC: &[u8; 8] = b"WUDB\0\0\0\x02";
+const HEADER_SIZE: u64 = MAGIC.len() as u64;
+const SLOT_SIZE: usize = 8 + 1 + 3 * 8;
+const MIN_CAPACITY: u64 = 1024;
+
+const SOLID: u8 = 1;
+const CONFIRMED: u8 = 2;
+const OTRSI: u8 = 4;
+const YTRSI: u8 = 8;
+const OCCUPIED: u8 = 16;
+
+/// A hash table on disk with a fixed-size slot per record and linear probing. Nothing but the size of the table is
+/// held in memory, and storing an id again overwrites its record in place, so the file only grows with the number
+/// of distinct ids. The table is rebuilt with twice the slots once it is half full.
+pub struct FileStorage {
+    path: PathBuf,
+    table: Mutex<Table>,
+}
+
+impl FileStorage {
+    /// Opens the store at `path` and keeps its records, or creates it if it doesn't exist yet.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+
+        let size = match fs::metadata(&path) {
+            Ok(metadata) if metadata.len() > 0 => metadata.len(),
+            _ => return Self::create(path),
+        };
+
+        let mut file = OpenOptions::new().read(true).write(true).open(&path)?;
+        let mut magic = [0; MAGIC.len()];
+        let capacity = size.saturating_sub(HEADER_SIZE) / SLOT_SIZE as u64;
+
+        if file.read_exact(&mut magic).is_err()
+            || &magic != MAGIC
+            || HEADER_SIZE + capacity * SLOT_SIZE as u64 != size
+            || !capacity.is_power_of_two()
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a storage file, or an unsupported version",
+            ));
+        }
+
+        let mut table = Table {
+            file,
+            capacity,
+            len: 0,
+        };
+        let mut len = 0;
+        table.for_each(|_, _| {
+            len += 1;
+            Ok(())
+        })?;
+        table.len = len;
+
+        Ok(Self {
+            path,
+            table: Mutex::new(table),
+        })
+    }
+
+    /// Creates an empty store at `path`, replacing whatever was there.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let table = Table::create(&path, MIN_CAPACITY)?;
+
+        Ok(Self {
+            path,
+            table: Mutex::new(table),
+        })
+    }
+
+    // NOTE: the records are copied into a new file next to the old one, which it then replaces; the suffix is
+    // appended, as replacing the extension would turn `tangle.grow` into the old file itself
+    fn grow(&self, table: &mut Table) -> io::Result<()> {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".grow");
+        let path = PathBuf::from(path);
+        let mut grown = Table::create(&path, table.capacity * 2)?;
+
+        table.for_each(|id, record| grown.insert(id, &record))?;
+
+        fs::rename(&path, &self.path)?;
+        *table = grown;
+
+        Ok(())
+    }
+}
+
+impl Storage for FileStorage {
+    fn get(&self, id: &InternedHash) -> io::Result<Option<Record>> {
+        let mut table = self.table.lock().expect("poisoned storage");
+
+        Ok(table.find(*id)?.1)
+    }
+
+    fn insert(&self, id: InternedHash, record: Record) -> io::Result<()> {
+        let mut table = self.table.lock().expect("poisoned storage");
+
+        if (table.len as u64 + 1) * 2 > table.capacity {
+            self.grow(&mut table)?;
+        }
+
+        table.insert(id, &record)
+    }
+
+    fn len(&self) -> usize {
+        self.table.lock().expect("poisoned storage").len
+    }
+}
+
+struct Table {
+    file: File,
+    capacity: u64,
+    len: usize,
+}
+
+impl Table {
+    fn create(path: &Path, capacity: u64) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+
+        // NOTE: the file is extended with zeros, which are empty slots
+        file.write_all(MAGIC)?;
+        file.set_len(HEADER_SIZE + capacity * SLOT_SIZE as u64)?;
+
+        Ok(Self {
+            file,
+            capacity,
+            len: 0,
+        })
+    }
+
+    // Returns the slot of `id` with its record, or the empty slot it goes into.
+    fn find(&mut self, id: InternedHash) -> io::Result<(u64, Option<Record>)> {
+        let mut slot = id.wrapping_mul(0x9e37_79b9_7f4a_7c15) % self.capacity;
+        let mut bytes = [0; SLOT_SIZE];
+
+        loop {
+            self.file
+                .seek(SeekFrom::Start(HEADER_SIZE + slot * SLOT_SIZE as u64))?;
+            self.file.read_exact(&mut bytes)?;
+
+            if bytes[8] & OCCUPIED == 0 {
+                return Ok((slot, None));
+            }
+
+            match decode(&bytes) {
+                (other, record) if other == id => return Ok((slot, Some(record))),
+                _ => slot = (slot + 1) % self.capacity,
+            }
+        }
+    }
+
+    fn insert(&mut self, id: InternedHash, record: &Record) -> io::Result<()> {
+        let (slot, old) = self.find(id)?;
+
+        self.file
+            .seek(SeekFrom::Start(HEADER_SIZE + slot * SLOT_SIZE as u64))?;
+        self.file.write_all(&encode(id, record))?;
+
+        if old.is_none() {
+            self.len += 1;
+        }
+
+        Ok(())
+    }
+
+    // NOTE: the slots are read sequentially, so that the records don't have to be held in memory all at once
+    fn for_each<F>(&mut self, mut f: F) -> io::Result<()>
+    where
+        F: FnMut(InternedHash, Record) -> io::Result<()>,
+    {
+        let mut bytes = [0; SLOT_SIZE];
+
+        self.file.seek(SeekFrom::Start(HEADER_SIZE))?;
+        let mut reader = BufReader::new(&self.file);
+
+        for _ in 0..self.capacity {
+            reader.read_exact(&mut bytes)?;
+
+            if bytes[8] & OCCUPIED != 0 {
+                let (id, record) = decode(&bytes);
+                f(id, record)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn encode(id: InternedHash, record: &Record) -> [u8; SLOT_SIZE] {
+    let mut bytes = [0; SLOT_SIZE];
+    let mut flags = OCCUPIED;
+
+    if record.solid {
+        flags |= SOLID;
+    }
+
+    for (i, (flag, value)) in [
+        (CONFIRMED, record.confirmed),
+        (OTRSI, record.otrsi),
+        (YTRSI, record.ytrsi),
+    ]
+    .iter()
+    .enumerate()
+    {
+        if let Some(value) = value {
+            flags |= flag;
+            bytes[9 + i * 8..17 + i * 8].copy_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    bytes[..8].copy_from_slice(&id.to_le_bytes());
+    bytes[8] = flags;
+
+    bytes
+}
+
+fn decode(bytes: &[u8]) -> (InternedHash, Record) {
+    let u64_at = |offset: usize| {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(&bytes[offset..offset + 8]);
+        u64::from_le_bytes(buf)
+    };
+    let flags = bytes[8];
+    let optional = |flag: u8, offset: usize| {
+        if flags & flag != 0 {
+            Some(u64_at(offset))
+        } else {
+            None
+        }
+    };
+
+    (
+        u64_at(0),
+        Record {
+            solid: flags & SOLID != 0,
+            confirmed: optional(CONFIRMED, 9),
+            otrsi: optional(OTRSI, 17),
+            ytrsi: optional(YTRSI, 25),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_storage_survives_reopening() {
+        // NOTE: the extension is the one of the file the table grows into
+        let path = std::env::temp_dir().join(format!("wurts-up-{}.grow", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let record = Record {
+            solid: true,
+            confirmed: None,
+            otrsi: Some(3),
+            ytrsi: Some(5),
+        };
+
+        {
+            let storage = FileStorage::create(&path).unwrap();
+            storage.insert(1, Record::default()).unwrap();
+            storage.insert(2, record).unwrap();
+            storage.insert(1, record).unwrap();
+        }
+
+        let size = fs::metadata(&path).unwrap().len();
+
+        {
+            let storage = FileStorage::open(&path).unwrap();
+            storage.insert(3, record).unwrap();
+
+            // NOTE: storing an id again overwrites its record
+            for _ in 0..10 {
+                storage.insert(3, record).unwrap();
+            }
+        }
+
+        assert_eq!(size, fs::metadata(&path).unwrap().len());
+
+        let storage = FileStorage::open(&path).unwrap();
+
+        assert_eq!(3, storage.len());
+        assert_eq!(Some(record), storage.get(&1).unwrap());
+        assert_eq!(Some(record), storage.get(&2).unwrap());
+        assert_eq!(Some(record), storage.get(&3).unwrap());
+        assert_eq!(None, storage.get(&4).unwrap());
+
+        // NOTE: the table grows once it is half full
+        for id in 4..=(MIN_CAPACITY / 2 + 1) {
+            storage.insert(id, Record::default()).unwrap();
+        }
+
+        assert_eq!((MIN_CAPACITY / 2 + 1) as usize, storage.len());
+        assert_eq!(Some(record), storage.get(&3).unwrap());
+        assert_eq!(
+            Some(Record::default()),
+            storage.get(&(MIN_CAPACITY / 2)).unwrap()
+        );
+        assert!(fs::metadata(&path).unwrap().len() > size);
+
+        // NOTE: a table, that was cut short behind our back, fails to read instead of panicking
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(HEADER_SIZE)
+            .unwrap();
+        assert!(storage.get(&3).is_err());
+        drop(storage);
+
+        assert_eq!(0, FileStorage::create(&path).unwrap().len());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
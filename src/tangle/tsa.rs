@@ -0,0 +1,413 @@
+use super::models::*;
+use super::{Promotion, Tangle};
+
+use rand::{rngs::StdRng, Rng};
+
+use crate::events::Event;
+
+use std::sync::atomic::Ordering;
+
+/// A tip selection algorithm (TSA), that picks a single tip from the tangle's tip pool.
+pub trait TipSelector: Send + Sync {
+    /// The name used to pick this strategy at startup.
+    fn name(&self) -> &'static str;
+
+    /// Selects a tip, or returns `None` if there is no eligible tip. All randomness must be drawn from `rng`, so that
+    /// a run can be reproduced from its seed.
+    fn select_tip(&self, tangle: &Tangle, rng: &mut StdRng) -> Option<InternedHash>;
+}
+
+/// Returns the strategy with the given name, if there is one.
+pub fn by_name(name: &str) -> Option<Box<dyn TipSelector>> {
+    match name {
+        "heuristic" => Some(Box::new(Heuristic)),
+        "urts" => Some(Box::new(Uniform)),
+        "mcmc" => Some(Box::new(RandomWalk::default())),
+        _ => match name.strip_prefix("mcmc:").map(|alpha| alpha.parse()) {
+            Some(Ok(alpha)) if alpha >= 0.0 && f64::is_finite(alpha) => {
+                Some(Box::new(RandomWalk::new(alpha)))
+            }
+            _ => None,
+        },
+    }
+}
+
+/// The OTRSI/YTRSI based heuristic from the Protocol RFC 0008: tips are picked at random with the weight of their
+/// score, and lazy tips are removed from the tip pool. Unless the promotion policy keeps them in the tip pool,
+/// semi-lazy tips are moved to the semi-lazy pool instead, from where they can be promoted.
+#[derive(Default)]
+pub struct Heuristic;
+
+impl TipSelector for Heuristic {
+    fn name(&self) -> &'static str {
+        "heuristic"
+    }
+
+    fn select_tip(&self, tangle: &Tangle, rng: &mut StdRng) -> Option<InternedHash> {
+        // From all the tips create a subset "solid tips"
+        let mut valid_tips = Vec::with_capacity(tangle.tips.len());
+        let mut score_sum = 0_isize;
+        let mut remove_list = Vec::new();
+        let mut semi_lazy_list = Vec::new();
+
+        if tangle.tips.is_empty() {
+            return None;
+        }
+
+        let weighted = tangle.promotion() == Promotion::Weighted;
+
+        // NOTE: the tip pool iterates in no particular order, so we sort it to make the pick reproducible
+        let mut tips = tangle.tips.iter().map(|id| *id).collect::<Vec<_>>();
+        tips.sort_unstable();
+
+        for id in tips {
+            // NOTE: copy the metadata, so that we don't hold the vertex while scoring it
+            let metadata = match tangle.get(&id) {
+                Some(metadata) => metadata,
+                None => continue,
+            };
+
+            // NOTE: a tip that isn't solid has no OTRSI/YTRSI yet, so it can't be scored
+            // TODO: think about the `solid` condition: what if a tip later becomes solid?
+            let score = if metadata.solid {
+                let otrsi = metadata.otrsi.expect("solid tip without otrsi").0;
+                let ytrsi = metadata.ytrsi.expect("solid tip without ytrsi").0;
+
+                tangle.get_tip_score(&id, otrsi, ytrsi) as isize
+            } else {
+                0
+            };
+
+            // NOTE: only non- and semi-lazy tips are considered for selection
+            if !metadata.solid || metadata.selected >= 2 || score == 0 {
+                remove_list.push(id);
+
+                continue;
+            }
+
+            if score == Score::SemiLazy as isize && !weighted {
+                semi_lazy_list.push(id);
+
+                continue;
+            }
+
+            //println!("[select_tip] Added a valid tip with id={}, score={}", id, score);
+
+            valid_tips.push((id, score));
+            score_sum += score;
+        }
+
+        for id in remove_list.iter() {
+            if tangle.tips.remove(id).is_some() {
+                tangle.events.emit(Event::TipRemoved { id: *id });
+            }
+        }
+
+        for id in semi_lazy_list.iter() {
+            tangle.add_semi_lazy(id);
+        }
+
+        if score_sum == 0 {
+            return None;
+        }
+
+        let mut random_number = rng.gen_range(1, score_sum + 1);
+
+        for (id, score) in valid_tips.iter() {
+            random_number -= score;
+            if random_number <= 0 {
+                if let Some(mut tip) = tangle.vertices.get_mut(id) {
+                    tip.metadata.selected += 1;
+                }
+
+                return Some(*id);
+            }
+        }
+
+        None
+    }
+}
+
+/// Uniform random tip selection (URTS): every solid tip above max depth is equally likely to be picked. It is the
+/// baseline, that shows what the scoring of the heuristic buys us.
+#[derive(Default)]
+pub struct Uniform;
+
+impl TipSelector for Uniform {
+    fn name(&self) -> &'static str {
+        "urts"
+    }
+
+    fn select_tip(&self, tangle: &Tangle, rng: &mut StdRng) -> Option<InternedHash> {
+        let lsmi = tangle.lsmi.load(Ordering::Relaxed);
+        let below_max_depth = tangle.tsa_config().below_max_depth;
+
+        let mut valid_tips = Vec::with_capacity(tangle.tips.len());
+        let mut remove_list = Vec::new();
+
+        // NOTE: the tip pool iterates in no particular order, so we sort it to make the pick reproducible
+        let mut tips = tangle.tips.iter().map(|id| *id).collect::<Vec<_>>();
+        tips.sort_unstable();
+
+        for id in tips {
+            let metadata = match tangle.get(&id) {
+                Some(metadata) if metadata.solid => metadata,
+                _ => continue,
+            };
+
+            let otrsi = metadata.otrsi.expect("solid tip without otrsi").0;
+            let ytrsi = metadata.ytrsi.expect("solid tip without ytrsi").0;
+
+            // NOTE: the score isn't used for the selection, but it makes the statistics comparable to the heuristic
+            tangle.get_tip_score(&id, otrsi, ytrsi);
+
+            // NOTE: a tip below max depth never rises above it again, as the LSMI only moves up
+            if lsmi.saturating_sub(otrsi) > below_max_depth {
+                remove_list.push(id);
+
+                continue;
+            }
+
+            valid_tips.push(id);
+        }
+
+        for id in remove_list.iter() {
+            if tangle.tips.remove(id).is_some() {
+                tangle.events.emit(Event::TipRemoved { id: *id });
+            }
+        }
+
+        if valid_tips.is_empty() {
+            return None;
+        }
+
+        let id = valid_tips[rng.gen_range(0, valid_tips.len())];
+
+        if let Some(mut tip) = tangle.vertices.get_mut(&id) {
+            tip.metadata.selected += 1;
+        }
+
+        Some(id)
+    }
+}
+
+/// The classic IOTA random walk (MCMC): starting at the latest solid milestone, it steps to one of the solid
+/// children of the current vertex, weighted by their cumulative weight, until it reaches a vertex without any. If
+/// the milestone isn't in memory (e.g. after starting from a snapshot), the walk starts at the solid entry points.
+pub struct RandomWalk {
+    // how strongly the walk prefers heavy children; 0 makes every step uniformly random
+    alpha: f64,
+}
+
+impl RandomWalk {
+    pub fn new(alpha: f64) -> Self {
+        Self { alpha }
+    }
+}
+
+impl Default for RandomWalk {
+    fn default() -> Self {
+        Self::new(0.1)
+    }
+}
+
+impl TipSelector for RandomWalk {
+    fn name(&self) -> &'static str {
+        "mcmc"
+    }
+
+    fn select_tip(&self, tangle: &Tangle, rng: &mut StdRng) -> Option<InternedHash> {
+        remove_below_max_depth(tangle);
+
+        let (mut id, mut children) = start(tangle);
+
+        loop {
+            // NOTE: the children iterate in no particular order, so we sort them to make the walk reproducible
+            children.sort_unstable();
+            children.retain(|child| tangle.is_solid(child));
+
+            if children.is_empty() {
+                break;
+            }
+
+            let weights = children
+                .iter()
+                .map(|child| tangle.cumulative_weight(child) as f64)
+                .collect::<Vec<_>>();
+            let max = weights.iter().cloned().fold(0.0, f64::max);
+
+            // NOTE: shifting by the heaviest child keeps `exp` from overflowing, and doesn't change the odds
+            let weights = weights
+                .iter()
+                .map(|weight| (self.alpha * (weight - max)).exp())
+                .collect::<Vec<_>>();
+            let sum = weights.iter().sum::<f64>();
+
+            let next = if sum > 0.0 && sum.is_finite() {
+                let mut random_number = rng.gen_range(0.0, sum);
+                let mut next = children[children.len() - 1];
+
+                for (child, weight) in children.iter().zip(weights.iter()) {
+                    random_number -= weight;
+                    if random_number < 0.0 {
+                        next = *child;
+                        break;
+                    }
+                }
+
+                next
+            } else {
+                // NOTE: if the weights don't add up to anything usable, every child is equally likely
+                children[rng.gen_range(0, children.len())]
+            };
+
+            children = match tangle.vertices.get(&next) {
+                Some(vertex) => vertex.children.iter().map(|child| *child).collect(),
+                None => Vec::new(),
+            };
+            id = Some(next);
+        }
+
+        let id = id?;
+
+        if let Some(mut tip) = tangle.vertices.get_mut(&id) {
+            tip.metadata.selected += 1;
+        }
+
+        Some(id)
+    }
+}
+
+// Where the walk starts: the latest solid milestone and its children, or else the children of the solid entry
+// points, which have to be looked up by their parents, as solid entry points aren't in memory.
+fn start(tangle: &Tangle) -> (Option<InternedHash>, Vec<InternedHash>) {
+    let lsmi = tangle.lsmi.load(Ordering::Relaxed);
+    let milestone = tangle.milestones.get(&lsmi).map(|id| *id);
+
+    if let Some(vertex) = milestone.and_then(|id| tangle.vertices.get(&id)) {
+        return (
+            milestone,
+            vertex.children.iter().map(|child| *child).collect(),
+        );
+    }
+
+    let is_entry = |id: &InternedHash| match milestone {
+        Some(milestone) if tangle.is_sep(&milestone) => *id == milestone,
+        _ => tangle.is_sep(id),
+    };
+
+    let children = tangle
+        .vertices
+        .iter()
+        .filter(|vertex| is_entry(&vertex.parents.ma) || is_entry(&vertex.parents.pa))
+        .map(|vertex| *vertex.key())
+        .collect();
+
+    (None, children)
+}
+
+// NOTE: the walk doesn't pick from the tip pool, but the pool is still kept free of tips below max depth, as with
+// the other selectors; such a tip never rises above it again, as the LSMI only moves up
+fn remove_below_max_depth(tangle: &Tangle) {
+    let lsmi = tangle.lsmi.load(Ordering::Relaxed);
+    let below_max_depth = tangle.tsa_config().below_max_depth;
+
+    let mut tips = tangle.tips.iter().map(|id| *id).collect::<Vec<_>>();
+    tips.sort_unstable();
+
+    for id in tips {
+        let below = match tangle.get_otrsi(&id) {
+            Some(otrsi) if tangle.is_solid(&id) => lsmi.saturating_sub(otrsi) > below_max_depth,
+            _ => false,
+        };
+
+        if below && tangle.tips.remove(&id).is_some() {
+            tangle.events.emit(Event::TipRemoved { id });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::Stats;
+    use crate::tangle::TsaConfig;
+    use crate::utils::make_tangle_1_milestone;
+
+    use rand::SeedableRng;
+    use std::sync::Arc;
+
+    #[test]
+    fn uniform_above_max_depth() {
+        let tangle = make_tangle_1_milestone().with_tsa_config(TsaConfig {
+            below_max_depth: 0,
+            ..TsaConfig::default()
+        });
+        let stats = Arc::new(Stats::default());
+        tangle.events.subscribe(Arc::clone(&stats) as _);
+
+        // NOTE: the OTRSI of 27 is 0, which is below max depth
+        tangle.insert_gossip(27, Message::default(), 0, 0);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut picked = [0; 4];
+
+        for _ in 0..100 {
+            let id = Uniform.select_tip(&tangle, &mut rng).unwrap();
+
+            assert!((23..=26).contains(&id));
+            picked[id as usize - 23] += 1;
+        }
+
+        // NOTE: tips are never removed for having been selected too often
+        assert!(picked.iter().all(|count| *count > 10));
+        assert_eq!(4, tangle.num_tips());
+
+        let summary = stats.summary(&tangle, 0);
+
+        assert_eq!(401, summary.lazy + summary.semi_lazy + summary.non_lazy);
+    }
+
+    #[test]
+    fn random_walk_ends_at_tips() {
+        let tangle = make_tangle_1_milestone();
+
+        // NOTE: milestone 1 is only approved by 19, which is approved by 22, 24, 25 and 26
+        assert_eq!(5, tangle.cumulative_weight(&19));
+        assert_eq!(1, tangle.cumulative_weight(&26));
+
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for alpha in [0.0, 0.1, 10.0, 1e300].iter() {
+            for _ in 0..100 {
+                let id = RandomWalk::new(*alpha)
+                    .select_tip(&tangle, &mut rng)
+                    .unwrap();
+
+                assert!((24..=26).contains(&id));
+            }
+        }
+
+        // NOTE: without the milestone in memory, the walk starts at the solid entry points
+        let restarted = Tangle::from_snapshot(&tangle.local_snapshot(1).unwrap());
+
+        for id in 13..=26 {
+            let vertex = tangle.vertices.get(&id).unwrap();
+            restarted.insert_gossip(id, Message::default(), vertex.parents.ma, vertex.parents.pa);
+        }
+
+        for _ in 0..100 {
+            let id = RandomWalk::default()
+                .select_tip(&restarted, &mut rng)
+                .unwrap();
+
+            assert!((24..=26).contains(&id));
+        }
+
+        assert!(by_name("mcmc:0.5").is_some());
+        assert!(by_name("mcmc:1e300").is_some());
+        assert!(by_name("mcmc:-1").is_none());
+        assert!(by_name("mcmc:inf").is_none());
+        assert!(by_name("mcmc:NaN").is_none());
+    }
+}